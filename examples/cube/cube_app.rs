@@ -1,6 +1,9 @@
 use wgpu_bootstrap::{
     cgmath, egui,
-    util::orbit_camera::{CameraUniform, OrbitCamera},
+    util::{
+        geometry::IndexBuffer,
+        orbit_camera::{CameraUniform, OrbitCamera},
+    },
     wgpu::{self, util::DeviceExt},
     App, Context,
 };
@@ -149,23 +152,14 @@ const INDEXES: &[u32] = &[
 
 pub struct CubeApp {
     vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    index_buffer: IndexBuffer,
     render_pipeline: wgpu::RenderPipeline,
-    num_indices: u32,
     camera: OrbitCamera,
 }
 
 impl CubeApp {
     pub fn new(context: &Context) -> Self {
-        let index_buffer = context
-            .device()
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(INDEXES),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-        let num_indices = INDEXES.len() as u32;
+        let index_buffer = IndexBuffer::new(context, INDEXES);
 
         let vertex_buffer =
             context
@@ -231,14 +225,16 @@ impl CubeApp {
                         conservative: false,
                     },
                     depth_stencil: Some(wgpu::DepthStencilState {
-                        format: context.depth_stencil_format(),
+                        format: context
+                            .depth_stencil_format()
+                            .expect("example requires a depth buffer"),
                         depth_write_enabled: true,
                         depth_compare: wgpu::CompareFunction::Less,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     }),
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: context.sample_count(),
                         mask: !0,
                         alpha_to_coverage_enabled: false,
                     },
@@ -262,7 +258,6 @@ impl CubeApp {
             vertex_buffer,
             index_buffer,
             render_pipeline,
-            num_indices,
             camera,
         }
     }
@@ -273,11 +268,11 @@ impl App for CubeApp {
         self.camera.input(input, context);
     }
 
-    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+    fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>, _context: &Context) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.index_buffer.bind(render_pass);
         render_pass.set_bind_group(0, self.camera.bind_group(), &[]);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+        render_pass.draw_indexed(0..self.index_buffer.num_indices(), 0, 0..1);
     }
 }
@@ -1,6 +1,9 @@
 use wgpu_bootstrap::{
     cgmath, egui,
-    util::orbit_camera::{CameraUniform, OrbitCamera},
+    util::{
+        geometry::IndexBuffer,
+        orbit_camera::{CameraUniform, OrbitCamera},
+    },
     wgpu::{self, util::DeviceExt},
     App, Context,
 };
@@ -149,24 +152,15 @@ const INDEXES: &[u32] = &[
 
 pub struct GuiApp {
     vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    index_buffer: IndexBuffer,
     render_pipeline: wgpu::RenderPipeline,
-    num_indices: u32,
     camera: OrbitCamera,
     fps: f32,
 }
 
 impl GuiApp {
     pub fn new(context: &Context) -> Self {
-        let index_buffer = context
-            .device()
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(INDEXES),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-        let num_indices = INDEXES.len() as u32;
+        let index_buffer = IndexBuffer::new(context, INDEXES);
 
         let vertex_buffer =
             context
@@ -232,14 +226,16 @@ impl GuiApp {
                         conservative: false,
                     },
                     depth_stencil: Some(wgpu::DepthStencilState {
-                        format: context.depth_stencil_format(),
+                        format: context
+                            .depth_stencil_format()
+                            .expect("example requires a depth buffer"),
                         depth_write_enabled: true,
                         depth_compare: wgpu::CompareFunction::Less,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     }),
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: context.sample_count(),
                         mask: !0,
                         alpha_to_coverage_enabled: false,
                     },
@@ -263,7 +259,6 @@ impl GuiApp {
             vertex_buffer,
             index_buffer,
             render_pipeline,
-            num_indices,
             camera,
             fps: 0.0,
         }
@@ -275,12 +270,12 @@ impl App for GuiApp {
         self.camera.input(input, context);
     }
 
-    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+    fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>, _context: &Context) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.index_buffer.bind(render_pass);
         render_pass.set_bind_group(0, self.camera.bind_group(), &[]);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+        render_pass.draw_indexed(0..self.index_buffer.num_indices(), 0, 0..1);
     }
 
     fn update(&mut self, delta_time: f32, _context: &Context) {
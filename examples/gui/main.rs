@@ -1,19 +1,14 @@
 mod gui_app;
 
-use std::sync::Arc;
-
 use crate::gui_app::GuiApp;
-use wgpu_bootstrap::{egui, Runner};
+use wgpu_bootstrap::{egui, BootstrapError, Runner};
 
-fn main() {
-    let mut runner = Runner::new(
-        "Gui App",
-        800,
-        600,
-        egui::Color32::from_rgb(245, 245, 245),
-        32,
-        0,
-        Box::new(|context| Arc::new(GuiApp::new(context))),
-    );
-    runner.run();
+fn main() -> Result<(), BootstrapError> {
+    let runner = Runner::builder()
+        .title("Gui App")
+        .size(800, 600)
+        .bg_color(egui::Color32::from_rgb(245, 245, 245))
+        .depth_buffer(32)
+        .build(Box::new(|context| Box::new(GuiApp::new(context))));
+    runner.run()
 }
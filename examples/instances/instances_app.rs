@@ -1,7 +1,7 @@
 use wgpu_bootstrap::{
     cgmath, egui,
     util::{
-        geometry::icosphere,
+        geometry::{icosphere, IndexBuffer},
         orbit_camera::{CameraUniform, OrbitCamera},
     },
     wgpu::{self, util::DeviceExt},
@@ -59,9 +59,8 @@ impl Instance {
 pub struct InstanceApp {
     vertex_buffer: wgpu::Buffer,
     instance_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    index_buffer: IndexBuffer,
     render_pipeline: wgpu::RenderPipeline,
-    num_indices: u32,
     num_instances: u32,
     camera: OrbitCamera,
 }
@@ -78,13 +77,7 @@ impl InstanceApp {
             })
             .collect();
 
-        let index_buffer = context
-            .device()
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices.as_slice()),
-                usage: wgpu::BufferUsages::INDEX,
-            });
+        let index_buffer = IndexBuffer::new(context, &indices);
 
         let instances: Vec<Instance> = positions
             .iter()
@@ -92,8 +85,6 @@ impl InstanceApp {
                 position: (*position).into(),
             })
             .collect();
-
-        let num_indices = indices.len() as u32;
         let num_instances = instances.len() as u32;
 
         let vertex_buffer =
@@ -169,14 +160,16 @@ impl InstanceApp {
                         conservative: false,
                     },
                     depth_stencil: Some(wgpu::DepthStencilState {
-                        format: context.depth_stencil_format(),
+                        format: context
+                            .depth_stencil_format()
+                            .expect("example requires a depth buffer"),
                         depth_write_enabled: true,
                         depth_compare: wgpu::CompareFunction::Less,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     }),
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: context.sample_count(),
                         mask: !0,
                         alpha_to_coverage_enabled: false,
                     },
@@ -195,7 +188,6 @@ impl InstanceApp {
             instance_buffer,
             index_buffer,
             render_pipeline,
-            num_indices,
             num_instances,
             camera,
         }
@@ -207,12 +199,12 @@ impl App for InstanceApp {
         self.camera.input(input, context);
     }
 
-    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+    fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>, _context: &Context) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.index_buffer.bind(render_pass);
         render_pass.set_bind_group(0, self.camera.bind_group(), &[]);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+        render_pass.draw_indexed(0..self.index_buffer.num_indices(), 0, 0..self.num_instances);
     }
 }
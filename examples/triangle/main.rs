@@ -1,19 +1,13 @@
 mod triangle_app;
 
-use std::sync::Arc;
-
 use crate::triangle_app::TriangleApp;
-use wgpu_bootstrap::{egui, Runner};
+use wgpu_bootstrap::{egui, BootstrapError, Runner};
 
-fn main() {
-    let mut runner = Runner::new(
-        "Triangle App",
-        800,
-        600,
-        egui::Color32::from_rgb(245, 245, 245),
-        0,
-        0,
-        Box::new(|context| Arc::new(TriangleApp::new(context))),
-    );
-    runner.run();
+fn main() -> Result<(), BootstrapError> {
+    let runner = Runner::builder()
+        .title("Triangle App")
+        .size(800, 600)
+        .bg_color(egui::Color32::from_rgb(245, 245, 245))
+        .build(Box::new(|context| Box::new(TriangleApp::new(context))));
+    runner.run()
 }
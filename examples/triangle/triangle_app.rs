@@ -113,7 +113,7 @@ impl TriangleApp {
                     },
                     depth_stencil: None,
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: context.sample_count(),
                         mask: !0,
                         alpha_to_coverage_enabled: false,
                     },
@@ -130,7 +130,7 @@ impl TriangleApp {
 }
 
 impl App for TriangleApp {
-    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+    fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>, _context: &Context) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.draw(0..self.num_vertices, 0..1);
@@ -1,6 +1,6 @@
 use wgpu_bootstrap::{
     util::{
-        geometry::{compute_line_list, icosphere},
+        geometry::{compute_line_list, icosphere, IndexBuffer},
         orbit_camera::{CameraUniform, OrbitCamera},
     },
     wgpu::{self, util::DeviceExt},
@@ -37,9 +37,8 @@ impl Vertex {
 
 pub struct WireframeApp {
     vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    index_buffer: IndexBuffer,
     render_pipeline: wgpu::RenderPipeline,
-    num_indices: u32,
     camera: OrbitCamera,
 }
 
@@ -57,15 +56,7 @@ impl WireframeApp {
 
         let indices = compute_line_list(indices);
 
-        let index_buffer = context
-            .device()
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices.as_slice()),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-        let num_indices = indices.len() as u32;
+        let index_buffer = IndexBuffer::new(context, &indices);
 
         let vertex_buffer =
             context
@@ -131,14 +122,16 @@ impl WireframeApp {
                         conservative: false,
                     },
                     depth_stencil: Some(wgpu::DepthStencilState {
-                        format: context.depth_stencil_format(),
+                        format: context
+                            .depth_stencil_format()
+                            .expect("example requires a depth buffer"),
                         depth_write_enabled: true,
                         depth_compare: wgpu::CompareFunction::Less,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     }),
                     multisample: wgpu::MultisampleState {
-                        count: 1,
+                        count: context.sample_count(),
                         mask: !0,
                         alpha_to_coverage_enabled: false,
                     },
@@ -156,7 +149,6 @@ impl WireframeApp {
             vertex_buffer,
             index_buffer,
             render_pipeline,
-            num_indices,
             camera,
         }
     }
@@ -167,11 +159,11 @@ impl App for WireframeApp {
         self.camera.input(input, context);
     }
 
-    fn render(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+    fn render(&mut self, render_pass: &mut wgpu::RenderPass<'_>, _context: &Context) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.index_buffer.bind(render_pass);
         render_pass.set_bind_group(0, self.camera.bind_group(), &[]);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+        render_pass.draw_indexed(0..self.index_buffer.num_indices(), 0, 0..1);
     }
 }
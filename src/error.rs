@@ -0,0 +1,74 @@
+use eframe::{egui_wgpu::WgpuError, wgpu};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum BootstrapError {
+    /// No adapter matching the requested backends and power preference was found.
+    NoAdapter,
+    /// The adapter refused to create a device with the requested features and limits.
+    RequestDevice {
+        features: wgpu::Features,
+        limits: Box<wgpu::Limits>,
+        source: wgpu::RequestDeviceError,
+    },
+    /// A depth buffer was used but the runner was built without one.
+    NoDepthBuffer,
+    Eframe(eframe::Error),
+}
+
+impl BootstrapError {
+    pub(crate) fn from_eframe(
+        error: eframe::Error,
+        features: wgpu::Features,
+        limits: &wgpu::Limits,
+    ) -> Self {
+        match error {
+            eframe::Error::Wgpu(WgpuError::NoSuitableAdapterFound) => Self::NoAdapter,
+            eframe::Error::Wgpu(WgpuError::RequestDeviceError(source)) => Self::RequestDevice {
+                features,
+                limits: Box::new(limits.clone()),
+                source,
+            },
+            eframe::Error::AppCreation(error) if error.is::<NoRenderState>() => Self::NoAdapter,
+            error => Self::Eframe(error),
+        }
+    }
+}
+
+impl fmt::Display for BootstrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter => write!(f, "no suitable WGPU adapter found"),
+            Self::RequestDevice {
+                features, source, ..
+            } => write!(
+                f,
+                "failed to create WGPU device with features {features:?} and the requested limits: {source}"
+            ),
+            Self::NoDepthBuffer => write!(f, "the runner was built without a depth buffer"),
+            Self::Eframe(error) => write!(f, "eframe error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BootstrapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::RequestDevice { source, .. } => Some(source),
+            Self::Eframe(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Returned from the eframe app creator when eframe did not set up wgpu.
+#[derive(Debug)]
+pub(crate) struct NoRenderState;
+
+impl fmt::Display for NoRenderState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "eframe did not create a WGPU render state")
+    }
+}
+
+impl std::error::Error for NoRenderState {}
@@ -0,0 +1,148 @@
+use eframe::{egui, wgpu};
+use pollster::block_on;
+
+use crate::{
+    error::BootstrapError,
+    framebuffer::Framebuffer,
+    runner::{App, Context, RunnerBuilder},
+    screenshot::{save_screenshot, ScreenshotKind, Screenshots},
+};
+
+const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+pub struct HeadlessRunner {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    width: u32,
+    height: u32,
+    bg_color: egui::Color32,
+    depth_stencil_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    framebuffer: Framebuffer,
+    screenshots: Screenshots,
+}
+
+impl HeadlessRunner {
+    pub(crate) fn new(settings: RunnerBuilder) -> Result<Self, BootstrapError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: settings.backends,
+            ..Default::default()
+        });
+
+        // Prefer a software adapter so the output does not depend on the GPU
+        // of the machine, but take whatever is available if there is none.
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: settings.power_preference,
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        }))
+        .or_else(|| {
+            block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: settings.power_preference,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            }))
+        })
+        .ok_or(BootstrapError::NoAdapter)?;
+
+        let (device, queue) = block_on(adapter.request_device(&settings.device_descriptor(), None))
+            .map_err(|source| BootstrapError::RequestDevice {
+                features: settings.features,
+                limits: Box::new(settings.limits.clone()),
+                source,
+            })?;
+
+        let width = settings.width;
+        let height = settings.height;
+        let depth_stencil_format = settings.depth_stencil_format();
+        let sample_count = settings.sample_count;
+
+        let framebuffer = Framebuffer::new(
+            &device,
+            width,
+            height,
+            COLOR_FORMAT,
+            depth_stencil_format,
+            sample_count,
+        );
+
+        Ok(Self {
+            device,
+            queue,
+            width,
+            height,
+            bg_color: settings.bg_color,
+            depth_stencil_format,
+            sample_count,
+            framebuffer,
+            screenshots: Screenshots::default(),
+        })
+    }
+
+    pub fn context(&self) -> Context<'_> {
+        Context {
+            device: &self.device,
+            queue: &self.queue,
+            size: egui::vec2(self.width as f32, self.height as f32),
+            format: COLOR_FORMAT,
+            depth_stencil_format: self.depth_stencil_format,
+            sample_count: self.sample_count,
+            screenshots: &self.screenshots,
+        }
+    }
+
+    /// Runs `frames` iterations of `update` and `render` with a fixed time
+    /// step of 1/60 s and returns the content of the last frame.
+    ///
+    /// Screenshots requested through the [`Context`] are taken at the end
+    /// of the frame. Both kinds give the same image as there is no GUI.
+    /// Fails if a frame cannot be read back from the GPU.
+    pub fn render(
+        &self,
+        app: &mut dyn App,
+        frames: u32,
+    ) -> Result<image::RgbaImage, wgpu::BufferAsyncError> {
+        let context = self.context();
+        let mut image = None;
+
+        for _ in 0..frames {
+            app.update(1.0 / 60.0, &context);
+            image = None;
+
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless Encoder"),
+                });
+            app.prepare(&context, &mut encoder);
+            app.encode(&mut encoder, &context);
+            self.framebuffer
+                .render(app, &context, &mut encoder, self.bg_color);
+            self.queue.submit(Some(encoder.finish()));
+
+            let mut paths = self.screenshots.take(ScreenshotKind::Viewport);
+            paths.extend(self.screenshots.take(ScreenshotKind::Full));
+            if !paths.is_empty() {
+                let frame = self.read()?;
+                for path in paths {
+                    save_screenshot(&frame, &path);
+                }
+                image = Some(frame);
+            }
+        }
+
+        match image {
+            Some(image) => Ok(image),
+            None => self.read(),
+        }
+    }
+
+    fn read(&self) -> Result<image::RgbaImage, wgpu::BufferAsyncError> {
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless Readback Encoder"),
+            });
+        self.framebuffer.read(&self.device, &self.queue, encoder)
+    }
+}
@@ -1,8 +1,18 @@
+pub mod error;
+mod framebuffer;
+pub mod headless;
 pub mod runner;
+pub mod screenshot;
+pub mod testing;
 pub mod util;
 pub use cgmath;
 pub use eframe::egui;
 pub use eframe::wgpu;
+pub use error::BootstrapError;
+pub use headless::HeadlessRunner;
+pub use image;
 pub use runner::App;
 pub use runner::Context;
 pub use runner::Runner;
+pub use runner::RunnerBuilder;
+pub use screenshot::ScreenshotKind;
@@ -3,16 +3,28 @@ use eframe::{
     egui_wgpu::{self, depth_format_from_bits, CallbackResources, CallbackTrait},
     wgpu,
 };
-use std::{sync::Arc, time::Instant};
-use pollster::block_on;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    error::{BootstrapError, NoRenderState},
+    framebuffer::{Blit, Framebuffer},
+    headless::HeadlessRunner,
+    screenshot::{save_screenshot, screenshot_path, ScreenshotKind, Screenshots},
+};
 
 #[allow(dead_code)]
 pub struct Context<'a> {
-    device: &'a wgpu::Device,
-    queue: &'a wgpu::Queue,
-    size: egui::Vec2,
-    format: wgpu::TextureFormat,
-    depth_stencil_format: Option<wgpu::TextureFormat>,
+    pub(crate) device: &'a wgpu::Device,
+    pub(crate) queue: &'a wgpu::Queue,
+    pub(crate) size: egui::Vec2,
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) depth_stencil_format: Option<wgpu::TextureFormat>,
+    pub(crate) sample_count: u32,
+    pub(crate) screenshots: &'a Screenshots,
 }
 
 impl<'a> Context<'a> {
@@ -32,13 +44,36 @@ impl<'a> Context<'a> {
         self.format
     }
 
-    pub fn depth_stencil_format(&self) -> wgpu::TextureFormat {
-        self.depth_stencil_format.unwrap()
+    pub fn depth_stencil_format(&self) -> Result<wgpu::TextureFormat, BootstrapError> {
+        self.depth_stencil_format
+            .ok_or(BootstrapError::NoDepthBuffer)
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Asks the runner to save a screenshot at the end of the current frame.
+    /// The image format is deduced from the extension of `path` (PNG or JPEG).
+    pub fn request_screenshot(&self, path: impl Into<PathBuf>, kind: ScreenshotKind) {
+        self.screenshots.request(path.into(), kind);
     }
 }
 
 pub trait App {
-    fn render(&self, _render_pass: &mut wgpu::RenderPass<'_>) {}
+    /// Called before the render pass starts. Commands recorded in `encoder`
+    /// are executed before anything is drawn, which makes it the place for
+    /// per-frame uploads.
+    fn prepare(&mut self, _context: &Context, _encoder: &mut wgpu::CommandEncoder) {}
+
+    /// Called after `prepare` with the same encoder. This is where offscreen
+    /// passes (shadow maps, reflections, compute) are recorded so that their
+    /// results can be used by `render`.
+    fn encode(&mut self, _encoder: &mut wgpu::CommandEncoder, _context: &Context) {}
+
+    /// Called once per frame inside the render pass the window is drawn in,
+    /// or the one of the framebuffer when a viewport screenshot is taken.
+    fn render(&mut self, _render_pass: &mut wgpu::RenderPass<'_>, _context: &Context) {}
 
     fn render_gui(&mut self, _egui_ctx: &egui::Context, _context: &Context) {}
 
@@ -49,116 +84,320 @@ pub trait App {
     fn resize(&mut self, _new_width: u32, _new_height: u32, _context: &Context) {}
 }
 
-pub struct Runner {
-    app_name: String,
-    width: u32,
-    height: u32,
-    bg_color: egui::Color32,
-    depth_buffer: u8,
-    stencil_buffer: u8,
-    limits: wgpu::Limits,
-    app_creator: Option<Box<dyn FnOnce(&Context) -> Arc<dyn App + Send + Sync>>>,
+pub type AppCreator = Box<dyn FnOnce(&Context) -> Box<dyn App + Send>>;
+
+pub struct RunnerBuilder {
+    pub(crate) title: String,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) resizable: bool,
+    pub(crate) present_mode: wgpu::PresentMode,
+    pub(crate) bg_color: egui::Color32,
+    pub(crate) depth_buffer: u8,
+    pub(crate) stencil_buffer: u8,
+    pub(crate) sample_count: u32,
+    pub(crate) features: wgpu::Features,
+    pub(crate) limits: wgpu::Limits,
+    pub(crate) power_preference: wgpu::PowerPreference,
+    pub(crate) backends: wgpu::Backends,
+    pub(crate) screenshot_key: Option<egui::Key>,
+    pub(crate) screenshot_kind: ScreenshotKind,
+    pub(crate) screenshot_dir: PathBuf,
+    pub(crate) screenshot_format: image::ImageFormat,
 }
 
-impl Runner {
-    pub fn new(
-        app_name: &str,
-        width: u32,
-        height: u32,
-        bg_color: egui::Color32,
-        depth_buffer: u8,
-        stencil_buffer: u8,
-        limits: wgpu::Limits,
-        app_creator: Box<dyn FnOnce(&Context) -> Arc<dyn App + Send + Sync>>,
-    ) -> Self {
+impl RunnerBuilder {
+    pub fn new() -> Self {
+        Self {
+            title: String::from("WGPU Bootstrap"),
+            width: 800,
+            height: 600,
+            resizable: true,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            bg_color: egui::Color32::from_rgb(245, 245, 245),
+            depth_buffer: 0,
+            stencil_buffer: 0,
+            sample_count: 1,
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            backends: wgpu::util::backend_bits_from_env()
+                .unwrap_or(wgpu::Backends::PRIMARY | wgpu::Backends::GL),
+            screenshot_key: Some(egui::Key::F12),
+            screenshot_kind: ScreenshotKind::Viewport,
+            screenshot_dir: PathBuf::from("."),
+            screenshot_format: image::ImageFormat::Png,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = String::from(title);
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.present_mode = if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn bg_color(mut self, bg_color: egui::Color32) -> Self {
+        self.bg_color = bg_color;
+        self
+    }
+
+    pub fn depth_buffer(mut self, bits: u8) -> Self {
+        self.depth_buffer = bits;
+        self
+    }
+
+    pub fn stencil_buffer(mut self, bits: u8) -> Self {
+        self.stencil_buffer = bits;
+        self
+    }
+
+    /// Number of MSAA samples. Pipelines must use the same count, which is
+    /// available from [`Context::sample_count`].
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn limits(mut self, limits: wgpu::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Key that saves a screenshot in the screenshot directory, `None` to
+    /// disable it. Defaults to F12.
+    pub fn screenshot_key(mut self, key: Option<egui::Key>) -> Self {
+        self.screenshot_key = key;
+        self
+    }
+
+    pub fn screenshot_kind(mut self, kind: ScreenshotKind) -> Self {
+        self.screenshot_kind = kind;
+        self
+    }
+
+    pub fn screenshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.screenshot_dir = dir.into();
+        self
+    }
+
+    /// Format of the screenshots taken with the screenshot key, either
+    /// `ImageFormat::Png` or `ImageFormat::Jpeg`.
+    pub fn screenshot_format(mut self, format: image::ImageFormat) -> Self {
+        self.screenshot_format = format;
+        self
+    }
+
+    pub fn build(self, app_creator: AppCreator) -> Runner {
         env_logger::init();
 
-        Self {
-            app_name: String::from(app_name),
-            width,
-            height,
-            bg_color,
-            app_creator: Some(app_creator),
-            depth_buffer,
-            stencil_buffer,
-            limits,
+        Runner {
+            settings: self,
+            app_creator,
         }
     }
 
-    pub fn run(&mut self) {
+    /// Creates a [`HeadlessRunner`] using the size, background color,
+    /// buffers and device settings of this builder. Window settings are
+    /// ignored.
+    pub fn build_headless(self) -> Result<HeadlessRunner, BootstrapError> {
+        HeadlessRunner::new(self)
+    }
+
+    pub(crate) fn device_descriptor(&self) -> wgpu::DeviceDescriptor<'static> {
+        wgpu::DeviceDescriptor {
+            label: Some("Custom Device"),
+            required_features: self.features,
+            required_limits: self.limits.clone(),
+            memory_hints: Default::default(),
+        }
+    }
+
+    pub(crate) fn depth_stencil_format(&self) -> Option<wgpu::TextureFormat> {
+        depth_format_from_bits(self.depth_buffer, self.stencil_buffer)
+    }
+}
+
+impl Default for RunnerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Runner {
+    settings: RunnerBuilder,
+    app_creator: AppCreator,
+}
+
+impl Runner {
+    pub fn builder() -> RunnerBuilder {
+        RunnerBuilder::new()
+    }
+
+    pub fn run(self) -> Result<(), BootstrapError> {
+        let settings = &self.settings;
+        let app_creator = self.app_creator;
+        let device_descriptor = settings.device_descriptor();
         let native_options = eframe::NativeOptions {
             viewport: egui::ViewportBuilder::default()
-                .with_inner_size((self.width as f32, self.height as f32)),
-            depth_buffer: self.depth_buffer,
-            stencil_buffer: self.stencil_buffer,
+                .with_title(&settings.title)
+                .with_inner_size((settings.width as f32, settings.height as f32))
+                .with_resizable(settings.resizable),
+            depth_buffer: settings.depth_buffer,
+            stencil_buffer: settings.stencil_buffer,
+            multisampling: settings.sample_count as u16,
+            wgpu_options: egui_wgpu::WgpuConfiguration {
+                supported_backends: settings.backends,
+                device_descriptor: Arc::new(move |_adapter| device_descriptor.clone()),
+                present_mode: settings.present_mode,
+                power_preference: settings.power_preference,
+                ..Default::default()
+            },
             ..Default::default()
         };
 
-        let depth_stencil_format = depth_format_from_bits(self.depth_buffer, self.stencil_buffer);
-
-        let _ = eframe::run_native(
-            &self.app_name,
+        eframe::run_native(
+            &settings.title,
             native_options,
             Box::new(|cc| {
+                let wgpu_render_state = cc.wgpu_render_state.as_ref().ok_or(NoRenderState)?;
                 Ok(Box::new(EframeApp::new(
-                    cc,
-                    self.width,
-                    self.height,
-                    self.bg_color,
-                    depth_stencil_format,
-                    self.app_creator.take().unwrap(),
+                    wgpu_render_state,
+                    settings,
+                    app_creator,
                 )))
             }),
-        );
+        )
+        .map_err(|error| BootstrapError::from_eframe(error, settings.features, &settings.limits))
     }
 }
 
+/// Everything needed to build a [`Context`] from inside the egui callbacks.
+struct Target {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    size: egui::Vec2,
+    format: wgpu::TextureFormat,
+    depth_stencil_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    bg_color: egui::Color32,
+    screenshots: Screenshots,
+}
+
+impl Target {
+    fn context(&self) -> Context<'_> {
+        Context {
+            device: &self.device,
+            queue: &self.queue,
+            size: self.size,
+            format: self.format,
+            depth_stencil_format: self.depth_stencil_format,
+            sample_count: self.sample_count,
+            screenshots: &self.screenshots,
+        }
+    }
+}
+
+/// The app is stored in egui's [`CallbackResources`] so that the paint
+/// callback can reach it without sharing it through an `Arc`.
+struct AppState {
+    target: Target,
+    // `paint` only gets a shared reference to the resources.
+    app: Mutex<Box<dyn App + Send>>,
+    // Created on the first viewport screenshot.
+    blit: Option<Blit>,
+    // The frame rendered for a viewport screenshot, shown instead of
+    // rendering the app a second time.
+    screenshot_frame: Option<wgpu::BindGroup>,
+}
+
 struct EframeApp {
-    window_width: u32,
-    window_height: u32,
     bg_color: egui::Color32,
-    depth_stencil_format: Option<wgpu::TextureFormat>,
     last: Option<Instant>,
-    app: Arc<dyn App + Send + Sync>,
+    screenshot_key: Option<egui::Key>,
+    screenshot_kind: ScreenshotKind,
+    screenshot_dir: PathBuf,
+    screenshot_format: image::ImageFormat,
+    // Paths waiting for the reply to a `ViewportCommand::Screenshot`.
+    pending_screenshots: Vec<PathBuf>,
 }
 
 impl EframeApp {
     fn new(
-        cc: &eframe::CreationContext<'_>,
-        width: u32,
-        height: u32,
-        bg_color: egui::Color32,
-        depth_stencil_format: Option<wgpu::TextureFormat>,
-        app_creator: Box<dyn FnOnce(&Context) -> Arc<dyn App + Send + Sync>>,
+        wgpu_render_state: &egui_wgpu::RenderState,
+        settings: &RunnerBuilder,
+        app_creator: AppCreator,
     ) -> Self {
-        let wgpu_render_state = cc.wgpu_render_state.as_ref().unwrap();
-        let adapter = wgpu_render_state.adapter.clone();
-        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-                lavel: Some("Custom Device"),
-                required_features: wgpu::Features::empty(),
-                required_limits: runner.limits,
-                memory_hints: Default::default(),
-            },
-            None,
-        ))
-        .expect("Failed to create WGPU device with correct limits");
-        let format = wgpu_render_state.target_format;
-
-        let context = Context {
-            device,
-            queue,
-            size: egui::vec2(width as f32, height as f32),
-            format,
-            depth_stencil_format,
+        let target = Target {
+            device: wgpu_render_state.device.clone(),
+            queue: wgpu_render_state.queue.clone(),
+            size: egui::vec2(settings.width as f32, settings.height as f32),
+            format: wgpu_render_state.target_format,
+            depth_stencil_format: settings.depth_stencil_format(),
+            sample_count: settings.sample_count,
+            bg_color: settings.bg_color,
+            screenshots: Screenshots::default(),
         };
 
+        let app = app_creator(&target.context());
+
+        wgpu_render_state
+            .renderer
+            .write()
+            .callback_resources
+            .insert(AppState {
+                target,
+                app: Mutex::new(app),
+                blit: None,
+                screenshot_frame: None,
+            });
+
         Self {
-            window_width: width,
-            window_height: height,
-            bg_color,
-            depth_stencil_format,
+            bg_color: settings.bg_color,
             last: None,
-            app: app_creator(&context),
+            screenshot_key: settings.screenshot_key,
+            screenshot_kind: settings.screenshot_kind,
+            screenshot_dir: settings.screenshot_dir.clone(),
+            screenshot_format: settings.screenshot_format,
+            pending_screenshots: Vec::new(),
         }
     }
 }
@@ -172,50 +411,64 @@ impl eframe::App for EframeApp {
         };
         self.last = Some(now);
 
-        let wgpu_render_state = frame.wgpu_render_state().unwrap();
-        let device = wgpu_render_state.device.clone();
-        let queue = wgpu_render_state.queue.clone();
-        let format = wgpu_render_state.target_format;
-        // let app: &mut Box<dyn App> = writer.callback_resources.get_mut().unwrap();
-        let mut context = Context {
-            device: device.as_ref(),
-            queue: queue.as_ref(),
-            size: egui::vec2(self.window_width as f32, self.window_height as f32),
-            format,
-            depth_stencil_format: self.depth_stencil_format,
+        let Some(wgpu_render_state) = frame.wgpu_render_state() else {
+            return;
         };
+        let mut renderer = wgpu_render_state.renderer.write();
+        let Some(AppState { target, app, .. }) = renderer.callback_resources.get_mut() else {
+            return;
+        };
+        let app = app.get_mut().unwrap();
 
-        if ctx.screen_rect().width() as u32 != self.window_width
-            || ctx.screen_rect().height() as u32 != self.window_height
-        {
-            self.window_width = ctx.screen_rect().width() as u32;
-            self.window_height = ctx.screen_rect().height() as u32;
-            context.size = egui::vec2(self.window_width as f32, self.window_height as f32);
-            Arc::get_mut(&mut self.app).unwrap().resize(
-                self.window_width,
-                self.window_height,
-                &context,
-            );
+        let size = ctx.screen_rect().size();
+        if size.x as u32 != target.size.x as u32 || size.y as u32 != target.size.y as u32 {
+            target.size = egui::vec2(size.x.floor(), size.y.floor());
+            app.resize(size.x as u32, size.y as u32, &target.context());
         }
+
+        let context = target.context();
         let input = ctx.input(|i| i.clone());
 
+        if !self.pending_screenshots.is_empty() {
+            for event in &input.events {
+                if let egui::Event::Screenshot { image, .. } = event {
+                    let image = color_image_to_rgba(image);
+                    for path in self.pending_screenshots.drain(..) {
+                        save_screenshot(&image, &path);
+                    }
+                }
+            }
+        }
+
+        if let Some(key) = self.screenshot_key {
+            if input.key_pressed(key) {
+                context.request_screenshot(
+                    screenshot_path(&self.screenshot_dir, self.screenshot_format),
+                    self.screenshot_kind,
+                );
+            }
+        }
+
         if !ctx.wants_pointer_input() && !ctx.wants_keyboard_input() {
-            Arc::get_mut(&mut self.app).unwrap().input(input, &context);
+            app.input(input, &context);
         }
 
-        Arc::get_mut(&mut self.app)
-            .unwrap()
-            .update(delta_time, &context);
+        app.update(delta_time, &context);
+
+        app.render_gui(ctx, &context);
+
+        let full_screenshots = target.screenshots.take(ScreenshotKind::Full);
+        if !full_screenshots.is_empty() {
+            self.pending_screenshots.extend(full_screenshots);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot);
+        }
 
-        Arc::get_mut(&mut self.app)
-            .unwrap()
-            .render_gui(ctx, &context);
+        drop(renderer);
 
         let container = egui::containers::Frame::default().fill(self.bg_color);
         egui::CentralPanel::default()
             .frame(container)
             .show(ctx, |ui| {
-                // egui::Frame::canvas(ctx.style()).show(ctx, |ui| {
                 let response = ui.allocate_space(egui::vec2(
                     ctx.screen_rect().width(),
                     ctx.screen_rect().height(),
@@ -223,32 +476,126 @@ impl eframe::App for EframeApp {
 
                 ui.painter().add(egui_wgpu::Callback::new_paint_callback(
                     response.1,
-                    WgpuCallback {
-                        app: self.app.clone(),
-                    },
+                    WgpuCallback { rect: response.1 },
                 ));
-                // });
             });
         ctx.request_repaint();
     }
 
     fn clear_color(&self, _visuals: &Visuals) -> [f32; 4] {
-        return [0.9, 0.4, 0.4, 1.0];
+        [0.9, 0.4, 0.4, 1.0]
     }
 }
 
 struct WgpuCallback {
-    app: Arc<dyn App + Send + Sync>,
+    /// Where egui paints the app, in points.
+    rect: egui::Rect,
 }
 
 impl CallbackTrait for WgpuCallback {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        screen_descriptor: &egui_wgpu::ScreenDescriptor,
+        _egui_encoder: &mut wgpu::CommandEncoder,
+        resources: &mut CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let Some(AppState {
+            target,
+            app,
+            blit,
+            screenshot_frame,
+        }) = resources.get_mut()
+        else {
+            return Vec::new();
+        };
+        let app = app.get_mut().unwrap();
+        let context = target.context();
+
+        // egui submits the returned command buffers before its own encoder,
+        // so everything recorded here is done when the render pass starts.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("App Encoder"),
+        });
+        app.prepare(&context, &mut encoder);
+        app.encode(&mut encoder, &context);
+
+        *screenshot_frame = None;
+        let screenshots = target.screenshots.take(ScreenshotKind::Viewport);
+        // The pixels `paint` will draw to, rounded like egui does.
+        let viewport = egui::PaintCallbackInfo {
+            viewport: self.rect,
+            clip_rect: self.rect,
+            pixels_per_point: screen_descriptor.pixels_per_point,
+            screen_size_px: screen_descriptor.size_in_pixels,
+        }
+        .viewport_in_pixels();
+        if screenshots.is_empty() || viewport.width_px <= 0 || viewport.height_px <= 0 {
+            return vec![encoder.finish()];
+        }
+
+        // Render the app into a framebuffer of the size of its viewport and
+        // wait for it, so the screenshot has no GUI on top. `paint` then
+        // copies that frame to the viewport instead of rendering it again.
+        let framebuffer = Framebuffer::new(
+            device,
+            viewport.width_px as u32,
+            viewport.height_px as u32,
+            target.format,
+            target.depth_stencil_format,
+            target.sample_count,
+        );
+        framebuffer.render(app.as_mut(), &context, &mut encoder, target.bg_color);
+        match framebuffer.read(device, queue, encoder) {
+            Ok(image) => {
+                for path in screenshots {
+                    save_screenshot(&image, &path);
+                }
+            }
+            Err(error) => log::error!("Failed to read the screenshot back: {error}"),
+        }
+        let blit = blit.get_or_insert_with(|| {
+            Blit::new(
+                device,
+                target.format,
+                target.depth_stencil_format,
+                target.sample_count,
+            )
+        });
+        *screenshot_frame = Some(blit.bind_group(device, &framebuffer));
+
+        Vec::new()
+    }
+
     fn paint(
         &self,
         _info: egui::PaintCallbackInfo,
         render_pass: &mut wgpu::RenderPass<'static>,
-        _resources: &CallbackResources,
+        resources: &CallbackResources,
     ) {
-        // let app: &Box<dyn App> = resources.get().unwrap();
-        self.app.render(render_pass);
+        let Some(AppState {
+            target,
+            app,
+            blit,
+            screenshot_frame,
+        }) = resources.get()
+        else {
+            return;
+        };
+        match (blit, screenshot_frame) {
+            (Some(blit), Some(frame)) => blit.draw(render_pass, frame),
+            _ => app.lock().unwrap().render(render_pass, &target.context()),
+        }
     }
 }
+
+fn color_image_to_rgba(image: &egui::ColorImage) -> image::RgbaImage {
+    let [width, height] = image.size;
+    let pixels = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_array())
+        .collect();
+    image::RgbaImage::from_raw(width as u32, height as u32, pixels).unwrap()
+}
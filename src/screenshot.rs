@@ -0,0 +1,59 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScreenshotKind {
+    /// Only what the app renders, without the egui widgets.
+    Viewport,
+    /// The whole window, egui widgets included.
+    Full,
+}
+
+/// Screenshots requested through [`Context::request_screenshot`](crate::Context::request_screenshot)
+/// and waiting for the runner to take them.
+#[derive(Default)]
+pub(crate) struct Screenshots {
+    requests: Mutex<Vec<(PathBuf, ScreenshotKind)>>,
+}
+
+impl Screenshots {
+    pub(crate) fn request(&self, path: PathBuf, kind: ScreenshotKind) {
+        self.requests.lock().unwrap().push((path, kind));
+    }
+
+    pub(crate) fn take(&self, kind: ScreenshotKind) -> Vec<PathBuf> {
+        let mut requests = self.requests.lock().unwrap();
+        let (taken, kept) = requests.drain(..).partition(|(_, k)| *k == kind);
+        *requests = kept;
+        taken.into_iter().map(|(path, _)| path).collect()
+    }
+}
+
+/// A path in `dir` named after the current time, with the extension of `format`.
+pub(crate) fn screenshot_path(dir: &Path, format: image::ImageFormat) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    let extension = format.extensions_str().first().unwrap_or(&"png");
+    dir.join(format!("screenshot-{millis}.{extension}"))
+}
+
+/// Saves `image` with the format given by the extension of `path`. Formats
+/// without an alpha channel, like JPEG, get the RGB channels only.
+pub(crate) fn save_screenshot(image: &image::RgbaImage, path: &Path) {
+    let result = match image::ImageFormat::from_path(path) {
+        Ok(image::ImageFormat::Jpeg) => image::DynamicImage::ImageRgba8(image.clone())
+            .to_rgb8()
+            .save(path),
+        _ => image.save(path),
+    };
+
+    match result {
+        Ok(()) => log::info!("Screenshot saved to {}", path.display()),
+        Err(error) => log::error!("Failed to save screenshot to {}: {error}", path.display()),
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+
+use crate::{headless::HeadlessRunner, runner::App};
+
+/// How far a render may drift from its golden image.
+#[derive(Debug, Copy, Clone)]
+pub struct Tolerance {
+    /// Largest perceived color difference, between 0 and 1, for two pixels
+    /// to be considered equal.
+    pub threshold: f32,
+    /// Fraction of the pixels, between 0 and 1, allowed to exceed `threshold`.
+    pub max_mismatch: f32,
+}
+
+impl Tolerance {
+    pub const EXACT: Tolerance = Tolerance {
+        threshold: 0.0,
+        max_mismatch: 0.0,
+    };
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            threshold: 0.05,
+            max_mismatch: 0.001,
+        }
+    }
+}
+
+/// Renders `frames` frames of `app` with `runner` and compares the last one
+/// to `tests/golden/<name>.png` in the crate being tested.
+///
+/// When the `UPDATE_GOLDEN` environment variable is set, the render is
+/// written as the new golden image instead. A missing golden image is an
+/// error, so a deleted or uncommitted one cannot pass silently. On
+/// mismatch, `<name>.actual.png` and `<name>.diff.png` are written next to
+/// it and the function panics.
+pub fn assert_render_matches(
+    name: &str,
+    runner: &HeadlessRunner,
+    app: &mut dyn App,
+    frames: u32,
+    tolerance: Tolerance,
+) {
+    let actual = runner
+        .render(app, frames)
+        .unwrap_or_else(|error| panic!("Failed to read the render of {name}: {error}"));
+
+    let dir = golden_dir();
+    let golden_path = dir.join(format!("{name}.png"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(&dir).unwrap();
+        actual.save(&golden_path).unwrap();
+        eprintln!("Golden image written to {}", golden_path.display());
+        return;
+    }
+    if !golden_path.exists() {
+        panic!(
+            "Golden image {} is missing, run the test with UPDATE_GOLDEN=1 to create it",
+            golden_path.display()
+        );
+    }
+
+    let golden = image::open(&golden_path)
+        .unwrap_or_else(|error| panic!("Failed to open {}: {error}", golden_path.display()))
+        .to_rgba8();
+
+    if golden.dimensions() != actual.dimensions() {
+        let actual_path = dir.join(format!("{name}.actual.png"));
+        actual.save(&actual_path).unwrap();
+        panic!(
+            "Render of {name} is {:?} but the golden image is {:?}, see {}",
+            actual.dimensions(),
+            golden.dimensions(),
+            actual_path.display()
+        );
+    }
+
+    let (mismatched, diff) = compare(&golden, &actual, tolerance.threshold);
+    let ratio = mismatched as f32 / (actual.width() * actual.height()) as f32;
+
+    if ratio > tolerance.max_mismatch {
+        let actual_path = dir.join(format!("{name}.actual.png"));
+        let diff_path = dir.join(format!("{name}.diff.png"));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "Render of {name} differs from {} on {mismatched} pixels ({:.3}%), see {} and {}",
+            golden_path.display(),
+            ratio * 100.0,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    let root = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    Path::new(&root).join("tests").join("golden")
+}
+
+/// Counts the pixels whose perceived difference is above `threshold` and
+/// returns an image showing them in red over a faded copy of `expected`.
+fn compare(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    threshold: f32,
+) -> (u32, image::RgbaImage) {
+    let mut mismatched = 0;
+    let mut diff = image::RgbaImage::new(expected.width(), expected.height());
+
+    for ((e, a), d) in expected
+        .pixels()
+        .zip(actual.pixels())
+        .zip(diff.pixels_mut())
+    {
+        if color_delta(e.0, a.0) > threshold {
+            mismatched += 1;
+            *d = image::Rgba([255, 0, 0, 255]);
+        } else {
+            let gray = 255 - ((255 - luma(e.0) as u32) / 4) as u8;
+            *d = image::Rgba([gray, gray, gray, 255]);
+        }
+    }
+
+    (mismatched, diff)
+}
+
+fn luma(pixel: [u8; 4]) -> u8 {
+    (0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32) as u8
+}
+
+/// Perceived difference between two colors in the YIQ space, weighted as in
+/// "Measuring perceived color difference using YIQ NTSC transmission color
+/// space in mobile applications" (Kotsarenko and Ramos), normalized to 0..1.
+fn color_delta(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let blend = |pixel: [u8; 4]| {
+        // Compare colors as if drawn over white.
+        let alpha = pixel[3] as f32 / 255.0;
+        [0, 1, 2].map(|i| 255.0 + (pixel[i] as f32 - 255.0) * alpha)
+    };
+    let [r1, g1, b1] = blend(a);
+    let [r2, g2, b2] = blend(b);
+
+    let y = |r: f32, g: f32, b: f32| r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_3;
+    let i = |r: f32, g: f32, b: f32| r * 0.595_977_9 - g * 0.274_176_3 - b * 0.321_801_6;
+    let q = |r: f32, g: f32, b: f32| r * 0.211_470_2 - g * 0.522_617_4 + b * 0.311_147_2;
+
+    let dy = y(r1, g1, b1) - y(r2, g2, b2);
+    let di = i(r1, g1, b1) - i(r2, g2, b2);
+    let dq = q(r1, g1, b1) - q(r2, g2, b2);
+
+    // 35215 is the largest possible value of the weighted sum.
+    (0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq) / 35215.0
+}
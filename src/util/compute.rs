@@ -0,0 +1,226 @@
+use eframe::wgpu::{self, util::DeviceExt};
+use std::sync::mpsc;
+
+use crate::runner::Context;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StorageAccess {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A compute pipeline whose bind group 0 is made of storage buffers, one per
+/// entry of the `bindings` given to [`ComputeRunner::new`], in binding order.
+pub struct ComputeRunner {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl ComputeRunner {
+    pub fn new(
+        context: &Context,
+        source: &str,
+        entry_point: &str,
+        bindings: &[StorageAccess],
+    ) -> Self {
+        let shader = context
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Compute Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+        let entries: Vec<wgpu::BindGroupLayoutEntry> = bindings
+            .iter()
+            .enumerate()
+            .map(|(binding, access)| wgpu::BindGroupLayoutEntry {
+                binding: binding as u32,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage {
+                        read_only: *access == StorageAccess::ReadOnly,
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect();
+
+        let bind_group_layout =
+            context
+                .device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Compute Bind Group Layout"),
+                    entries: &entries,
+                });
+
+        let pipeline_layout =
+            context
+                .device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Compute Pipeline Layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let pipeline = context
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Compute Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    pub fn bind_group(&self, context: &Context, buffers: &[&wgpu::Buffer]) -> wgpu::BindGroup {
+        let entries: Vec<wgpu::BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+
+        context
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Compute Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &entries,
+            })
+    }
+
+    /// Records the dispatch in `encoder`, for use from [`App::encode`](crate::App::encode).
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        workgroups: (u32, u32, u32),
+    ) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+    }
+
+    /// Dispatches the shader and submits it right away, for use from
+    /// [`App::update`](crate::App::update) or outside of any app.
+    pub fn run(&self, context: &Context, buffers: &[&wgpu::Buffer], workgroups: (u32, u32, u32)) {
+        let bind_group = self.bind_group(context, buffers);
+        let mut encoder =
+            context
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Compute Encoder"),
+                });
+        self.dispatch(&mut encoder, &bind_group, workgroups);
+        context.queue().submit(Some(encoder.finish()));
+    }
+}
+
+/// Creates a buffer usable as a storage binding that can also be written
+/// from the CPU and read back with [`read_buffer`].
+pub fn create_storage_buffer<T: bytemuck::Pod>(context: &Context, data: &[T]) -> wgpu::Buffer {
+    context
+        .device()
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Storage Buffer"),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        })
+}
+
+/// Copies `buffer` to the CPU and waits for the GPU to be done with it.
+/// The buffer needs the `COPY_SRC` usage. Fails when the copy cannot be
+/// mapped, for instance after the device is lost.
+pub fn read_buffer<T: bytemuck::Pod>(
+    context: &Context,
+    buffer: &wgpu::Buffer,
+) -> Result<Vec<T>, wgpu::BufferAsyncError> {
+    let staging_buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Staging Buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = context
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Read Buffer Encoder"),
+        });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+    context.queue().submit(Some(encoder.finish()));
+
+    let slice = staging_buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    context.device().poll(wgpu::Maintain::Wait);
+    // The callback has run once `poll` returns. A closed channel means it
+    // was dropped without being called.
+    receiver.recv().map_err(|_| wgpu::BufferAsyncError)??;
+
+    let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    staging_buffer.unmap();
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::RunnerBuilder;
+
+    const DOUBLE: &str = r"
+@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> output: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x < arrayLength(&input)) {
+        output[id.x] = 2.0 * input[id.x];
+    }
+}
+";
+
+    #[test]
+    fn doubles_values() {
+        let Ok(runner) = RunnerBuilder::new().build_headless() else {
+            eprintln!("no WGPU adapter available, skipping");
+            return;
+        };
+        let context = runner.context();
+
+        let values: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let input = create_storage_buffer(&context, &values);
+        let output = create_storage_buffer(&context, &vec![0.0_f32; values.len()]);
+
+        let compute = ComputeRunner::new(
+            &context,
+            DOUBLE,
+            "main",
+            &[StorageAccess::ReadOnly, StorageAccess::ReadWrite],
+        );
+        compute.run(&context, &[&input, &output], (2, 1, 1));
+
+        let result: Vec<f32> = read_buffer(&context, &output).unwrap();
+        let expected: Vec<f32> = values.iter().map(|v| 2.0 * v).collect();
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,289 @@
+use cgmath::{prelude::*, Vector3};
+use std::collections::{HashMap, HashSet};
+
+use super::Mesh;
+
+impl Mesh {
+    /// Merges vertices whose positions, and every other attribute the mesh
+    /// has, are within `epsilon` of each other. Seams where the UVs or the
+    /// normals differ are kept; set those attributes to `None` first to
+    /// weld across them. Merged vertices are removed.
+    pub fn weld(&mut self, epsilon: f32) -> &mut Self {
+        let (remap, sources) = self.weld_map(epsilon);
+        let indices = self
+            .indices
+            .iter()
+            .map(|&index| remap[index as usize])
+            .collect();
+        *self = self.remapped(&sources, indices);
+        self
+    }
+
+    /// The vertex each vertex is merged into by [`weld`](Self::weld), and
+    /// the source of each merged vertex, which is the first one merged.
+    pub(crate) fn weld_map(&self, epsilon: f32) -> (Vec<u32>, Vec<u32>) {
+        let epsilon = epsilon.max(0.0);
+        let close = |a: u32, b: u32| -> bool {
+            let (a, b) = (a as usize, b as usize);
+            let near = |u: Vector3<f32>, v: Vector3<f32>| (u - v).magnitude2() <= epsilon * epsilon;
+            near(self.positions[a], self.positions[b])
+                && self
+                    .normals
+                    .as_ref()
+                    .is_none_or(|normals| near(normals[a], normals[b]))
+                && self
+                    .uvs
+                    .as_ref()
+                    .is_none_or(|uvs| (uvs[a] - uvs[b]).magnitude2() <= epsilon * epsilon)
+                && self
+                    .tangents
+                    .as_ref()
+                    .is_none_or(|tangents| near(tangents[a], tangents[b]))
+                && self
+                    .colors
+                    .as_ref()
+                    .is_none_or(|colors| near(colors[a], colors[b]))
+        };
+
+        // Positions are bucketed in cells of size `epsilon`, so only the
+        // neighboring cells need to be searched.
+        let cell = |position: Vector3<f32>| -> [i64; 3] {
+            if epsilon > 0.0 {
+                [position.x, position.y, position.z].map(|c| (c / epsilon).floor() as i64)
+            } else {
+                [position.x, position.y, position.z].map(|c| c.to_bits() as i64)
+            }
+        };
+        let reach: &[i64] = if epsilon > 0.0 { &[-1, 0, 1] } else { &[0] };
+
+        let mut cells: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.positions.len());
+        let mut sources = Vec::new();
+        for vertex in 0..self.positions.len() as u32 {
+            let [x, y, z] = cell(self.positions[vertex as usize]);
+            let mut found = None;
+            'search: for dx in reach {
+                for dy in reach {
+                    for dz in reach {
+                        let neighbors = cells.get(&[x + dx, y + dy, z + dz]);
+                        for &kept in neighbors.into_iter().flatten() {
+                            if close(sources[kept as usize], vertex) {
+                                found = Some(kept);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+
+            let kept = found.unwrap_or_else(|| {
+                sources.push(vertex);
+                let kept = sources.len() as u32 - 1;
+                cells.entry([x, y, z]).or_default().push(kept);
+                kept
+            });
+            remap.push(kept);
+        }
+        (remap, sources)
+    }
+
+    /// Removes the triangles using a vertex twice or whose area is negligible
+    /// compared to the square of their longest edge.
+    pub fn remove_degenerate_triangles(&mut self) -> &mut Self {
+        let positions = &self.positions;
+        self.indices = self
+            .indices
+            .chunks_exact(3)
+            .filter(|triangle| !is_degenerate(positions, [triangle[0], triangle[1], triangle[2]]))
+            .flatten()
+            .copied()
+            .collect();
+        self
+    }
+
+    /// Removes the triangles using the same vertices in the same order as
+    /// an earlier one. A triangle and its flipped copy are both kept, since
+    /// they face opposite ways.
+    pub fn remove_duplicate_triangles(&mut self) -> &mut Self {
+        let mut seen = HashSet::new();
+        self.indices = self
+            .indices
+            .chunks_exact(3)
+            .filter(|triangle| {
+                // Rotate the smallest index first, which keeps the winding.
+                let first = (0..3).min_by_key(|&k| triangle[k]).unwrap();
+                seen.insert([0, 1, 2].map(|k| triangle[(first + k) % 3]))
+            })
+            .flatten()
+            .copied()
+            .collect();
+        self
+    }
+
+    /// Removes the vertices no triangle uses and compacts the indices. The
+    /// remaining vertices keep their order.
+    ///
+    /// Meshes without indices are point clouds and are left untouched.
+    pub fn remove_unreferenced_vertices(&mut self) -> &mut Self {
+        if self.indices.is_empty() {
+            return self;
+        }
+
+        let mut used = vec![false; self.positions.len()];
+        for &index in &self.indices {
+            used[index as usize] = true;
+        }
+
+        let mut remap = vec![0; self.positions.len()];
+        let mut sources = Vec::new();
+        for (vertex, used) in used.into_iter().enumerate() {
+            if used {
+                remap[vertex] = sources.len() as u32;
+                sources.push(vertex as u32);
+            }
+        }
+
+        let indices = self
+            .indices
+            .iter()
+            .map(|&index| remap[index as usize])
+            .collect();
+        *self = self.remapped(&sources, indices);
+        self
+    }
+
+    /// Welds the vertices within `epsilon`, then removes the degenerate and
+    /// duplicate triangles and the vertices left unused.
+    pub fn clean(&mut self, epsilon: f32) -> &mut Self {
+        self.weld(epsilon)
+            .remove_degenerate_triangles()
+            .remove_duplicate_triangles()
+            .remove_unreferenced_vertices()
+    }
+}
+
+/// The first vertex at the position of each vertex. Generators and
+/// exporters rarely give the copies of a vertex on a seam exactly the same
+/// position, so positions a tiny fraction of the size of the mesh apart
+/// count as the same.
+pub(crate) fn position_groups(positions: &[Vector3<f32>]) -> Vec<u32> {
+    let extent = positions.iter().fold(0.0f32, |extent, position| {
+        extent.max(position.x.abs().max(position.y.abs()).max(position.z.abs()))
+    });
+    let (remap, sources) = Mesh::new(positions.to_vec(), Vec::new()).weld_map(extent * 1e-5);
+    remap.iter().map(|&kept| sources[kept as usize]).collect()
+}
+
+/// Whether `triangle` uses a vertex twice or has an area that is negligible
+/// compared to the square of its longest edge.
+pub(crate) fn is_degenerate(positions: &[Vector3<f32>], triangle: [u32; 3]) -> bool {
+    let [a, b, c] = triangle;
+    if a == b || b == c || c == a {
+        return true;
+    }
+    let [a, b, c] = triangle.map(|index| positions[index as usize]);
+    let longest = (b - a)
+        .magnitude2()
+        .max((c - b).magnitude2())
+        .max((a - c).magnitude2());
+    (b - a).cross(c - a).magnitude() <= 1e-6 * longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::{compute_normal_vectors, cuboid, uv_sphere};
+
+    fn positions_only(mesh: &Mesh) -> Mesh {
+        Mesh::new(mesh.positions.clone(), mesh.indices.clone())
+    }
+
+    #[test]
+    fn welds_positions() {
+        let mut cube = positions_only(&cuboid(1.0, 1.0, 1.0));
+        cube.weld(1e-5);
+        assert_eq!(cube.vertex_count(), 8);
+        assert_eq!(cube.triangle_count(), 12);
+
+        // The seams of a sphere are kept while its UVs are, and the pole
+        // rings collapse to single vertices without them.
+        let mut sphere = uv_sphere(1.0, 16, 8);
+        let count = sphere.vertex_count();
+        sphere.weld(1e-5);
+        assert_eq!(sphere.vertex_count(), count);
+
+        let mut sphere = positions_only(&sphere);
+        sphere.weld(1e-5);
+        assert_eq!(sphere.vertex_count(), 16 * 7 + 2);
+    }
+
+    #[test]
+    fn welds_within_epsilon_only() {
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1.0005, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+        ];
+        let mesh = Mesh::new(positions, vec![0, 1, 2, 3, 4, 2]);
+
+        let mut welded = mesh.clone();
+        welded.weld(1e-3);
+        assert_eq!(welded.vertex_count(), 4);
+        assert_eq!(welded.indices, vec![0, 1, 2, 1, 3, 2]);
+
+        let mut exact = mesh.clone();
+        exact.weld(0.0);
+        assert_eq!(exact.vertex_count(), 5);
+    }
+
+    #[test]
+    fn cleans_triangles_and_vertices() {
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(5.0, 5.0, 5.0),
+        ];
+        let indices = vec![
+            0, 1, 2, // kept
+            1, 2, 0, // same triangle, rotated
+            0, 2, 1, // flipped, kept
+            0, 0, 1, // repeated vertex
+            0, 1, 3, // collinear
+        ];
+        let mut mesh = Mesh::new(positions, indices);
+        mesh.remove_degenerate_triangles();
+        assert_eq!(mesh.triangle_count(), 3);
+        mesh.remove_duplicate_triangles();
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 1]);
+        mesh.remove_unreferenced_vertices();
+        assert_eq!(mesh.vertex_count(), 3);
+    }
+
+    #[test]
+    fn clean_chains_everything() {
+        let mut cube = positions_only(&cuboid(2.0, 2.0, 2.0));
+        cube.indices.extend_from_within(0..3);
+        cube.positions.push(Vector3::new(9.0, 9.0, 9.0));
+        cube.clean(1e-5);
+        assert_eq!(cube.vertex_count(), 8);
+        assert_eq!(cube.triangle_count(), 12);
+    }
+
+    #[test]
+    fn degenerate_triangles_give_no_nan() {
+        let mut positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(3.0, 3.0, 3.0),
+        ];
+        let indices = vec![0, 1, 2, 3, 3, 3];
+        let normals = compute_normal_vectors(&mut positions, &indices);
+        assert_eq!(normals[0], Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(normals[3], Vector3::new(0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+/// Error returned when reading a model file.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    /// A texture embedded in or referenced by the model could not be decoded.
+    Image(image::ImageError),
+    /// The file is malformed. `line` is set for text formats.
+    Parse {
+        line: Option<usize>,
+        message: String,
+    },
+}
+
+impl LoadError {
+    pub(crate) fn parse(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read model: {error}"),
+            Self::Image(error) => write!(f, "failed to decode model texture: {error}"),
+            Self::Parse {
+                line: Some(line),
+                message,
+            } => write!(f, "invalid model on line {line}: {message}"),
+            Self::Parse {
+                line: None,
+                message,
+            } => write!(f, "invalid model: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<image::ImageError> for LoadError {
+    fn from(error: image::ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+impl From<gltf::Error> for LoadError {
+    fn from(error: gltf::Error) -> Self {
+        match error {
+            gltf::Error::Io(error) => Self::Io(error),
+            gltf::Error::Image(error) => Self::Image(error),
+            error => Self::parse(None, error.to_string()),
+        }
+    }
+}
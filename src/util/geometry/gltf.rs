@@ -0,0 +1,955 @@
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector3, Vector4};
+use gltf::{
+    accessor::{DataType, Dimensions},
+    buffer,
+    mesh::{Mode, Semantic},
+    Accessor, Gltf, Primitive,
+};
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::{LoadError, Mesh};
+
+/// The content of a glTF 2.0 file, with the buffers already split into
+/// meshes and the images decoded.
+#[derive(Debug, Clone, Default)]
+pub struct GltfScene {
+    pub nodes: Vec<GltfNode>,
+    /// Top-level nodes of the default scene.
+    pub roots: Vec<usize>,
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    /// Decoded images, referenced by index from the materials.
+    pub images: Vec<image::RgbaImage>,
+}
+
+impl GltfScene {
+    /// The transform of each node relative to the scene, identity for the
+    /// nodes outside of it.
+    pub fn world_transforms(&self) -> Vec<Matrix4<f32>> {
+        let mut transforms = vec![Matrix4::identity(); self.nodes.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, Matrix4::identity()))
+            .collect();
+        while let Some((node, parent)) = stack.pop() {
+            transforms[node] = parent * self.nodes[node].transform;
+            for &child in &self.nodes[node].children {
+                stack.push((child, transforms[node]));
+            }
+        }
+        transforms
+    }
+
+    /// Each mesh to draw in the scene, as `(mesh index, world transform)`.
+    pub fn mesh_instances(&self) -> Vec<(usize, Matrix4<f32>)> {
+        let transforms = self.world_transforms();
+        let mut instances = Vec::new();
+        let mut stack = self.roots.clone();
+        while let Some(node) = stack.pop() {
+            if let Some(mesh) = self.nodes[node].mesh {
+                instances.push((mesh, transforms[node]));
+            }
+            stack.extend_from_slice(&self.nodes[node].children);
+        }
+        instances
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfNode {
+    pub name: String,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    /// Transform relative to the parent node.
+    pub transform: Matrix4<f32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GltfMesh {
+    pub name: String,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfPrimitive {
+    pub mesh: Mesh,
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fragments with an alpha below `alpha_cutoff` are discarded.
+    Mask,
+    Blend,
+}
+
+/// A metallic-roughness material. Textures are indices into
+/// [`GltfScene::images`]; the base color and emissive ones are sRGB, the
+/// others linear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfMaterial {
+    pub name: String,
+    pub base_color_factor: Vector4<f32>,
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Roughness in the green channel, metalness in the blue one.
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<usize>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: Vector3<f32>,
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+/// Reads a `.gltf` or `.glb` file. Buffers and images stored in separate
+/// files are looked up next to it.
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<GltfScene, LoadError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    parse(&bytes, Some(path.parent().unwrap_or(Path::new(""))))
+}
+
+/// Parses a `.gltf` or `.glb` file already loaded in memory. Everything
+/// must be embedded, as data URIs or in the binary chunk.
+pub fn parse_gltf(bytes: &[u8]) -> Result<GltfScene, LoadError> {
+    parse(bytes, None)
+}
+
+fn invalid(message: impl Into<String>) -> LoadError {
+    LoadError::parse(None, message)
+}
+
+fn parse(bytes: &[u8], dir: Option<&Path>) -> Result<GltfScene, LoadError> {
+    let Gltf { document, mut blob } = Gltf::from_slice(bytes)?;
+
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            buffer::Source::Uri(uri) => read_uri(uri, dir)?,
+            buffer::Source::Bin => blob
+                .take()
+                .ok_or_else(|| invalid("buffer 0 has no URI and there is no binary chunk"))?,
+        };
+        if data.len() < buffer.length() {
+            return Err(invalid(format!(
+                "buffer {} is shorter than its byteLength",
+                buffer.index()
+            )));
+        }
+        buffers.push(data);
+    }
+
+    let mut images = Vec::new();
+    for image in document.images() {
+        let data = match image.source() {
+            gltf::image::Source::Uri { uri, .. } => read_uri(uri, dir)?,
+            gltf::image::Source::View { view, .. } => buffer_view(&buffers, &view)?.to_vec(),
+        };
+        images.push(image::load_from_memory(&data)?.to_rgba8());
+    }
+
+    let materials = document.materials().map(parse_material).collect();
+
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            if let Some(mesh) = read_primitive(&buffers, &primitive)? {
+                primitives.push(GltfPrimitive {
+                    mesh,
+                    material: primitive.material().index(),
+                });
+            }
+        }
+        meshes.push(GltfMesh {
+            name: mesh.name().unwrap_or_default().to_owned(),
+            primitives,
+        });
+    }
+
+    let nodes: Vec<GltfNode> = document.nodes().map(parse_node).collect();
+    check_hierarchy(&nodes)?;
+
+    let roots = match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        // Without scenes, every node without a parent is a root.
+        None => (0..nodes.len())
+            .filter(|&node| !nodes.iter().any(|parent| parent.children.contains(&node)))
+            .collect(),
+    };
+
+    Ok(GltfScene {
+        nodes,
+        roots,
+        meshes,
+        materials,
+        images,
+    })
+}
+
+/// Checks that the nodes form trees: each node has at most one parent and
+/// is not its own ancestor.
+fn check_hierarchy(nodes: &[GltfNode]) -> Result<(), LoadError> {
+    let mut parents = vec![None; nodes.len()];
+    for (parent, node) in nodes.iter().enumerate() {
+        for &child in &node.children {
+            if parents[child].replace(parent).is_some() {
+                return Err(invalid(format!("node {child} has several parents")));
+            }
+        }
+    }
+    for node in 0..nodes.len() {
+        let mut ancestor = parents[node];
+        for _ in 0..nodes.len() {
+            match ancestor {
+                Some(parent) if parent == node => {
+                    return Err(invalid(format!("node {node} is its own ancestor")))
+                }
+                Some(parent) => ancestor = parents[parent],
+                None => break,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_uri(uri: &str, dir: Option<&Path>) -> Result<Vec<u8>, LoadError> {
+    // Data and `file:` URIs are handled by the gltf crate, which panics on
+    // malformed percent-escapes in relative ones.
+    if uri.contains(':') {
+        return Ok(buffer::Data::from_source(buffer::Source::Uri(uri), dir)?.0);
+    }
+
+    let dir = dir.ok_or_else(|| invalid(format!("external file `{uri}` cannot be loaded")))?;
+    let path = urlencoding::decode(uri).map_err(|_| invalid(format!("invalid URI `{uri}`")))?;
+    Ok(std::fs::read(dir.join(&*path))?)
+}
+
+fn parse_node(node: gltf::Node) -> GltfNode {
+    GltfNode {
+        name: node.name().unwrap_or_default().to_owned(),
+        children: node.children().map(|child| child.index()).collect(),
+        mesh: node.mesh().map(|mesh| mesh.index()),
+        // Column-major, like cgmath.
+        transform: node.transform().matrix().into(),
+    }
+}
+
+fn parse_material(material: gltf::Material) -> GltfMaterial {
+    // Materials point to textures, which point to images.
+    let source = |texture: gltf::Texture| texture.source().index();
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+
+    GltfMaterial {
+        name: material.name().unwrap_or_default().to_owned(),
+        base_color_factor: pbr.base_color_factor().into(),
+        base_color_texture: pbr.base_color_texture().map(|info| source(info.texture())),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| source(info.texture())),
+        normal_texture: normal.as_ref().map(|normal| source(normal.texture())),
+        normal_scale: normal.as_ref().map_or(1.0, |normal| normal.scale()),
+        occlusion_texture: occlusion
+            .as_ref()
+            .map(|occlusion| source(occlusion.texture())),
+        occlusion_strength: occlusion
+            .as_ref()
+            .map_or(1.0, |occlusion| occlusion.strength()),
+        emissive_factor: material.emissive_factor().into(),
+        emissive_texture: material
+            .emissive_texture()
+            .map(|info| source(info.texture())),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
+
+/// The bytes of a buffer view.
+fn buffer_view<'a>(buffers: &'a [Vec<u8>], view: &buffer::View) -> Result<&'a [u8], LoadError> {
+    let buffer = &buffers[view.buffer().index()];
+    view.offset()
+        .checked_add(view.length())
+        .and_then(|end| buffer.get(view.offset()..end))
+        .ok_or_else(|| invalid("buffer view out of its buffer"))
+}
+
+/// Reads an accessor as `count` elements of `dimensions().multiplicity()`
+/// numbers each. Normalized integers are mapped to 0..1 or -1..1.
+fn read_accessor(buffers: &[Vec<u8>], accessor: &Accessor) -> Result<Vec<f64>, LoadError> {
+    if accessor.sparse().is_some() {
+        return Err(invalid("sparse accessors are not supported"));
+    }
+
+    let count = accessor.count();
+    let components = accessor.dimensions().multiplicity();
+    let data_type = accessor.data_type();
+    let size = data_type.size();
+
+    let Some(view) = accessor.view() else {
+        // Zeros, which cannot take more room than the data of the file.
+        let total: usize = buffers.iter().map(Vec::len).sum();
+        return match count.checked_mul(size * components) {
+            Some(length) if length <= total => Ok(vec![0.0; count * components]),
+            _ => Err(invalid("accessor larger than the buffers")),
+        };
+    };
+    let bytes = buffer_view(buffers, &view)?;
+    let offset = accessor.offset();
+    let stride = view.stride().unwrap_or(size * components);
+
+    // The last element has to end in the view, which also keeps the
+    // offsets below from overflowing.
+    let end = match count.checked_sub(1) {
+        None => Some(offset),
+        Some(last) => last
+            .checked_mul(stride)
+            .and_then(|start| start.checked_add(offset))
+            .and_then(|start| start.checked_add(size * components)),
+    };
+    if end.is_none_or(|end| end > bytes.len()) {
+        return Err(invalid("accessor out of its buffer view"));
+    }
+
+    let normalized = accessor.normalized();
+    let mut values = Vec::with_capacity(count * components);
+    for element in 0..count {
+        for component in 0..components {
+            let start = offset + element * stride + component * size;
+            let bytes = &bytes[start..start + size];
+            values.push(match data_type {
+                DataType::I8 if normalized => ((bytes[0] as i8) as f64 / 127.0).max(-1.0),
+                DataType::I8 => (bytes[0] as i8) as f64,
+                DataType::U8 if normalized => bytes[0] as f64 / 255.0,
+                DataType::U8 => bytes[0] as f64,
+                DataType::I16 => {
+                    let value = i16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+                    match normalized {
+                        true => (value / 32767.0).max(-1.0),
+                        false => value,
+                    }
+                }
+                DataType::U16 => {
+                    let value = u16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+                    match normalized {
+                        true => value / 65535.0,
+                        false => value,
+                    }
+                }
+                DataType::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                DataType::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            });
+        }
+    }
+    Ok(values)
+}
+
+/// Converts a primitive into a triangle list. Points and lines are skipped.
+fn read_primitive(buffers: &[Vec<u8>], primitive: &Primitive) -> Result<Option<Mesh>, LoadError> {
+    let mode = primitive.mode();
+    if !matches!(
+        mode,
+        Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+    ) {
+        log::warn!("Skipping glTF primitive with mode {mode:?}, only triangles are supported");
+        return Ok(None);
+    }
+
+    // Reads an attribute with one of the `allowed` types.
+    let attribute = |semantic: Semantic, allowed: &[Dimensions]| {
+        let Some(accessor) = primitive.get(&semantic) else {
+            return Ok(None);
+        };
+        let dimensions = accessor.dimensions();
+        if !allowed.contains(&dimensions) {
+            return Err(invalid(format!(
+                "invalid accessor type for {}",
+                semantic.to_string()
+            )));
+        }
+        Ok(Some((
+            read_accessor(buffers, &accessor)?,
+            dimensions.multiplicity(),
+        )))
+    };
+    let vec3 = |(values, components): (Vec<f64>, usize)| -> Vec<Vector3<f32>> {
+        values
+            .chunks_exact(components)
+            .map(|v| Vector3::new(v[0] as f32, v[1] as f32, v[2] as f32))
+            .collect()
+    };
+
+    let positions = attribute(Semantic::Positions, &[Dimensions::Vec3])?
+        .map(vec3)
+        .ok_or_else(|| invalid("primitive without positions"))?;
+    let count = positions.len();
+
+    let strip: Vec<u32> = match primitive.indices() {
+        Some(accessor) => {
+            let unsigned = matches!(
+                accessor.data_type(),
+                DataType::U8 | DataType::U16 | DataType::U32
+            );
+            if accessor.dimensions() != Dimensions::Scalar || !unsigned || accessor.normalized() {
+                return Err(invalid("indices must be unsigned integer scalars"));
+            }
+            read_accessor(buffers, &accessor)?
+                .into_iter()
+                .map(|value| value as u32)
+                .collect()
+        }
+        None => (0..count as u32).collect(),
+    };
+    let indices: Vec<u32> = match mode {
+        // Strips alternate winding, fans turn around the first vertex.
+        Mode::TriangleStrip => (2..strip.len())
+            .flat_map(|i| match i % 2 {
+                0 => [strip[i - 2], strip[i - 1], strip[i]],
+                _ => [strip[i - 1], strip[i - 2], strip[i]],
+            })
+            .collect(),
+        Mode::TriangleFan => (2..strip.len())
+            .flat_map(|i| [strip[0], strip[i - 1], strip[i]])
+            .collect(),
+        _ => strip,
+    };
+    if indices.iter().any(|&index| index as usize >= count) {
+        return Err(invalid("primitive index out of range"));
+    }
+
+    let mut mesh = Mesh::new(positions, indices);
+    mesh.normals = attribute(Semantic::Normals, &[Dimensions::Vec3])?.map(vec3);
+    mesh.tangents = attribute(Semantic::Tangents, &[Dimensions::Vec3, Dimensions::Vec4])?.map(vec3);
+    mesh.colors = attribute(Semantic::Colors(0), &[Dimensions::Vec3, Dimensions::Vec4])?.map(vec3);
+    mesh.uvs =
+        attribute(Semantic::TexCoords(0), &[Dimensions::Vec2])?.map(|(values, components)| {
+            values
+                .chunks_exact(components)
+                .map(|v| Vector2::new(v[0] as f32, v[1] as f32))
+                .collect()
+        });
+
+    let lengths = [
+        mesh.normals.as_ref().map(Vec::len),
+        mesh.tangents.as_ref().map(Vec::len),
+        mesh.colors.as_ref().map(Vec::len),
+        mesh.uvs.as_ref().map(Vec::len),
+    ];
+    if lengths.into_iter().flatten().any(|length| length != count) {
+        return Err(invalid("attributes have different counts"));
+    }
+    if mesh.normals.is_none() {
+        mesh.compute_normals();
+    }
+
+    Ok(Some(mesh))
+}
+
+pub fn save_glb(mesh: &Mesh, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_glb(mesh, &mut writer)?;
+    writer.flush()
+}
+
+/// Writes `mesh` as a binary glTF file holding one node with one mesh and
+/// the default material. Meshes without indices are written as points.
+///
+/// [`Mesh`] does not store the handedness of the tangent frames, so the
+/// tangents are written with a `w` of 1.
+pub fn write_glb(mesh: &Mesh, writer: &mut impl Write) -> std::io::Result<()> {
+    let floats = |values: &mut dyn Iterator<Item = f32>| -> Vec<u8> {
+        values.flat_map(f32::to_le_bytes).collect()
+    };
+    let vec3 = |vectors: &[Vector3<f32>]| floats(&mut vectors.iter().flat_map(|v| [v.x, v.y, v.z]));
+
+    let mut buffer = GlbBuffer::default();
+    let mut attributes = serde_json::Map::new();
+
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for position in &mesh.positions {
+        for (k, value) in [position.x, position.y, position.z].into_iter().enumerate() {
+            min[k] = min[k].min(value);
+            max[k] = max[k].max(value);
+        }
+    }
+    let bounds = (!mesh.positions.is_empty()).then_some((min, max));
+    let positions = buffer.accessor(&vec3(&mesh.positions), mesh.positions.len(), "VEC3", bounds);
+    attributes.insert("POSITION".to_owned(), positions.into());
+
+    if let Some(normals) = &mesh.normals {
+        let normals = buffer.accessor(&vec3(normals), normals.len(), "VEC3", None);
+        attributes.insert("NORMAL".to_owned(), normals.into());
+    }
+    if let Some(tangents) = &mesh.tangents {
+        let bytes = floats(&mut tangents.iter().flat_map(|v| [v.x, v.y, v.z, 1.0]));
+        let tangents = buffer.accessor(&bytes, tangents.len(), "VEC4", None);
+        attributes.insert("TANGENT".to_owned(), tangents.into());
+    }
+    if let Some(uvs) = &mesh.uvs {
+        let bytes = floats(&mut uvs.iter().flat_map(|v| [v.x, v.y]));
+        let uvs = buffer.accessor(&bytes, uvs.len(), "VEC2", None);
+        attributes.insert("TEXCOORD_0".to_owned(), uvs.into());
+    }
+    if let Some(colors) = &mesh.colors {
+        let colors = buffer.accessor(&vec3(colors), colors.len(), "VEC3", None);
+        attributes.insert("COLOR_0".to_owned(), colors.into());
+    }
+
+    let mut primitive = json!({ "attributes": attributes });
+    if mesh.indices.is_empty() {
+        primitive["mode"] = json!(0);
+    } else {
+        let bytes: Vec<u8> = mesh.indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        primitive["indices"] = buffer.indices(&bytes, mesh.indices.len()).into();
+    }
+
+    let json = json!({
+        "asset": { "version": "2.0", "generator": "wgpu-bootstrap" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [primitive] }],
+        "buffers": [{ "byteLength": buffer.bytes.len() }],
+        "bufferViews": buffer.views,
+        "accessors": buffer.accessors,
+    });
+
+    let mut json = json.to_string().into_bytes();
+    while !json.len().is_multiple_of(4) {
+        json.push(b' ');
+    }
+    let bin = buffer.bytes;
+
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+    writer.write_all(&(bin.len() as u32).to_le_bytes())?;
+    writer.write_all(b"BIN\0")?;
+    writer.write_all(&bin)
+}
+
+/// The binary chunk of a GLB file being written, with its buffer views and
+/// accessors.
+#[derive(Default)]
+struct GlbBuffer {
+    bytes: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GlbBuffer {
+    const ARRAY_BUFFER: usize = 34962;
+    const ELEMENT_ARRAY_BUFFER: usize = 34963;
+    const FLOAT: usize = 5126;
+    const UNSIGNED_INT: usize = 5125;
+
+    fn view(&mut self, data: &[u8], target: usize) -> usize {
+        let offset = self.bytes.len();
+        self.bytes.extend_from_slice(data);
+        while !self.bytes.len().is_multiple_of(4) {
+            self.bytes.push(0);
+        }
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        self.views.len() - 1
+    }
+
+    /// Adds float data, with the bounds required for positions.
+    fn accessor(
+        &mut self,
+        data: &[u8],
+        count: usize,
+        kind: &str,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> usize {
+        let view = self.view(data, Self::ARRAY_BUFFER);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": Self::FLOAT,
+            "count": count,
+            "type": kind,
+        });
+        if let Some((min, max)) = bounds {
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn indices(&mut self, data: &[u8], count: usize) -> usize {
+        let view = self.view(data, Self::ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": Self::UNSIGNED_INT,
+            "count": count,
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
+        for chunk in bytes.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+                bits | (byte as u32) << (16 - 8 * i)
+            });
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    /// A triangle: three VEC3 float positions followed by three u16 indices.
+    fn triangle_buffer() -> Vec<u8> {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let indices: [u16; 3] = [0, 1, 2];
+        let mut buffer = bytemuck::cast_slice::<f32, u8>(&positions).to_vec();
+        buffer.extend_from_slice(bytemuck::cast_slice(&indices));
+        buffer
+    }
+
+    const TRIANGLE_VIEWS: &str = r#"
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "meshes": [
+            { "name": "triangle", "primitives": [
+                { "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }
+            ] }
+        ]"#;
+
+    #[test]
+    fn parses_embedded_gltf() {
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{
+                    "byteLength": 42,
+                    "uri": "data:application/octet-stream;base64,{}"
+                }}],
+                {TRIANGLE_VIEWS},
+                "materials": [{{
+                    "name": "red",
+                    "pbrMetallicRoughness": {{
+                        "baseColorFactor": [1, 0, 0, 1],
+                        "metallicFactor": 0
+                    }},
+                    "alphaMode": "MASK"
+                }}],
+                "nodes": [
+                    {{ "name": "parent", "translation": [1, 0, 0], "children": [1] }},
+                    {{ "name": "child", "scale": [2, 2, 2], "mesh": 0 }}
+                ],
+                "scenes": [{{ "nodes": [0] }}],
+                "scene": 0
+            }}"#,
+            encode_base64(&triangle_buffer())
+        );
+
+        let scene = parse_gltf(json.as_bytes()).unwrap();
+
+        let mesh = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(scene.meshes[0].name, "triangle");
+        assert_eq!(mesh.positions[1], Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(
+            mesh.normals.as_ref().unwrap()[0],
+            Vector3::new(0.0, 0.0, 1.0)
+        );
+
+        let material = &scene.materials[0];
+        assert_eq!(material.base_color_factor, Vector4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(material.metallic_factor, 0.0);
+        assert_eq!(material.roughness_factor, 1.0);
+        assert_eq!(material.alpha_mode, AlphaMode::Mask);
+
+        let instances = scene.mesh_instances();
+        assert_eq!(instances.len(), 1);
+        let (mesh, transform) = instances[0];
+        assert_eq!(mesh, 0);
+        let corner = transform * Vector4::new(1.0, 0.0, 0.0, 1.0);
+        assert_eq!(corner, Vector4::new(3.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn parses_glb_with_embedded_image() {
+        let image = image::RgbaImage::from_pixel(4, 2, image::Rgba([0, 255, 0, 255]));
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let mut bin = triangle_buffer();
+        bin.resize(44, 0);
+        let image_offset = bin.len();
+        bin.extend_from_slice(&png);
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+
+        let views = TRIANGLE_VIEWS.replacen(
+            r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }"#,
+            &format!(
+                r#"{{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }},
+                   {{ "buffer": 0, "byteOffset": {image_offset}, "byteLength": {} }}"#,
+                png.len()
+            ),
+            1,
+        );
+        let mut json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": {} }}],
+                {views},
+                "images": [{{ "bufferView": 2, "mimeType": "image/png" }}],
+                "textures": [{{ "source": 0 }}],
+                "materials": [{{
+                    "pbrMetallicRoughness": {{ "baseColorTexture": {{ "index": 0 }} }}
+                }}],
+                "nodes": [{{ "mesh": 0 }}]
+            }}"#,
+            bin.len()
+        )
+        .into_bytes();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+
+        let scene = parse_gltf(&glb).unwrap();
+        assert_eq!(scene.images.len(), 1);
+        assert_eq!(scene.images[0].dimensions(), (4, 2));
+        assert_eq!(scene.images[0].get_pixel(0, 0).0, [0, 255, 0, 255]);
+        assert_eq!(scene.materials[0].base_color_texture, Some(0));
+        assert_eq!(scene.roots, vec![0]);
+        assert_eq!(scene.meshes[0].primitives[0].mesh.triangle_count(), 1);
+    }
+
+    #[test]
+    fn written_glb_parses_back() {
+        let mut mesh = crate::util::geometry::capsule(0.5, 1.0, 12, 3);
+        mesh.set_color(Vector3::new(1.0, 0.5, 0.0));
+
+        let mut bytes = Vec::new();
+        write_glb(&mesh, &mut bytes).unwrap();
+        let scene = parse_gltf(&bytes).unwrap();
+
+        assert_eq!(scene.roots, vec![0]);
+        assert_eq!(scene.mesh_instances(), vec![(0, Matrix4::identity())]);
+        let loaded = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(loaded.positions, mesh.positions);
+        assert_eq!(loaded.normals, mesh.normals);
+        assert_eq!(loaded.uvs, mesh.uvs);
+        assert_eq!(loaded.tangents, mesh.tangents);
+        assert_eq!(loaded.colors, mesh.colors);
+        assert_eq!(loaded.indices, mesh.indices);
+    }
+
+    /// An embedded glTF file with the triangle buffer, a default material and
+    /// the given views, accessors and meshes.
+    fn triangle_gltf(views: &str) -> Vec<u8> {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{
+                    "byteLength": 42,
+                    "uri": "data:application/octet-stream;base64,{}"
+                }}],
+                "materials": [{{}}],
+                {views}
+            }}"#,
+            encode_base64(&triangle_buffer())
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn rejects_attributes_with_the_wrong_type() {
+        assert!(parse_gltf(&triangle_gltf(TRIANGLE_VIEWS)).is_ok());
+
+        // The indices, as SCALAR normals.
+        let views = TRIANGLE_VIEWS.replace(r#""POSITION": 0 }"#, r#""POSITION": 0, "NORMAL": 1 }"#);
+        assert!(matches!(
+            parse_gltf(&triangle_gltf(&views)),
+            Err(LoadError::Parse { .. })
+        ));
+
+        // The positions, as VEC3 texture coordinates.
+        let views =
+            TRIANGLE_VIEWS.replace(r#""POSITION": 0 }"#, r#""POSITION": 0, "TEXCOORD_0": 0 }"#);
+        assert!(matches!(
+            parse_gltf(&triangle_gltf(&views)),
+            Err(LoadError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_sizes_past_the_buffers() {
+        let huge_count = TRIANGLE_VIEWS.replace(
+            r#""count": 3, "type": "VEC3""#,
+            r#""count": 4000000000, "type": "VEC3""#,
+        );
+        let huge_offset = TRIANGLE_VIEWS.replace(
+            r#""byteOffset": 36"#,
+            r#""byteOffset": 18446744073709551615"#,
+        );
+        let huge_stride = TRIANGLE_VIEWS.replace(
+            r#""byteOffset": 0, "byteLength": 36"#,
+            r#""byteOffset": 0, "byteLength": 36, "byteStride": 9223372036854775807"#,
+        );
+        let no_view = TRIANGLE_VIEWS.replace(
+            r#""bufferView": 0, "componentType": 5126, "count": 3"#,
+            r#""componentType": 5126, "count": 4000000000"#,
+        );
+        for views in [huge_count, huge_offset, huge_stride, no_view] {
+            assert!(matches!(
+                parse_gltf(&triangle_gltf(&views)),
+                Err(LoadError::Parse { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_external_buffers_without_a_path() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 4, "uri": "data.bin" }]
+        }"#;
+        assert!(matches!(
+            parse_gltf(json.as_bytes()),
+            Err(LoadError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_node_cycles() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "nodes": [{ "children": [1] }, { "children": [0] }]
+        }"#;
+        assert!(matches!(
+            parse_gltf(json.as_bytes()),
+            Err(LoadError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_indices_with_the_wrong_type() {
+        // The positions, as indices.
+        let views = TRIANGLE_VIEWS.replace(r#""indices": 1"#, r#""indices": 0"#);
+        assert!(matches!(
+            parse_gltf(&triangle_gltf(&views)),
+            Err(LoadError::Parse { .. })
+        ));
+
+        // The indices, as signed shorts.
+        let views = TRIANGLE_VIEWS.replace(
+            r#""componentType": 5123, "count": 3"#,
+            r#""componentType": 5122, "count": 3"#,
+        );
+        assert!(matches!(
+            parse_gltf(&triangle_gltf(&views)),
+            Err(LoadError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_deeply_nested_json() {
+        let json = format!(
+            r#"{{ "asset": {{ "version": "2.0" }}, "extras": {} }}"#,
+            "[".repeat(100_000)
+        );
+        assert!(matches!(
+            parse_gltf(json.as_bytes()),
+            Err(LoadError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_percent_encoded_uris() {
+        let dir = std::env::temp_dir().join(format!("wgpu-bootstrap-gltf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tri angle+1.bin"), triangle_buffer()).unwrap();
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": 42, "uri": "tri%20angle%2B1.bin" }}],
+                "materials": [{{}}],
+                {TRIANGLE_VIEWS}
+            }}"#
+        );
+        std::fs::write(dir.join("triangle.gltf"), &json).unwrap();
+        let bad_escape = json.replace("%2B", "%G1");
+        std::fs::write(dir.join("bad.gltf"), bad_escape).unwrap();
+
+        let scene = load_gltf(dir.join("triangle.gltf"));
+        let bad = load_gltf(dir.join("bad.gltf"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            scene.unwrap().meshes[0].primitives[0].mesh.indices,
+            vec![0, 1, 2]
+        );
+        assert!(bad.is_err());
+    }
+}
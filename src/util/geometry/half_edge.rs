@@ -0,0 +1,365 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{edge_key, Mesh};
+
+/// Connectivity of a triangle list, stored as half-edges.
+///
+/// Half-edge `h` goes from corner `h % 3` to the next corner of triangle
+/// `h / 3`, so half-edges are numbered like the indices they start from.
+/// Triangles keep the counter-clockwise winding of the indices.
+///
+/// An edge has a twin only when it is shared by exactly two triangles going
+/// through it in opposite directions. Other edges, including non-manifold
+/// and inconsistently wound ones, are treated as boundaries.
+#[derive(Debug, Clone)]
+pub struct HalfEdgeMesh {
+    corners: Vec<u32>,
+    twins: Vec<Option<u32>>,
+    outgoing: Vec<Vec<u32>>,
+}
+
+impl HalfEdgeMesh {
+    /// Builds the connectivity of `indices`, whose values must be less than
+    /// `vertex_count`. Trailing indices are ignored.
+    pub fn new(vertex_count: usize, indices: &[u32]) -> Self {
+        let corners = indices[..indices.len() - indices.len() % 3].to_vec();
+
+        let mut outgoing = vec![Vec::new(); vertex_count];
+        let mut directed: HashMap<u64, Vec<u32>> = HashMap::new();
+        for half_edge in 0..corners.len() as u32 {
+            let (a, b) = (
+                corners[half_edge as usize],
+                corners[next(half_edge) as usize],
+            );
+            outgoing[a as usize].push(half_edge);
+            directed
+                .entry(directed_key(a, b))
+                .or_default()
+                .push(half_edge);
+        }
+
+        let mut twins = vec![None; corners.len()];
+        for (&key, half_edges) in &directed {
+            let (a, b) = ((key >> 32) as u32, key as u32);
+            if let ([half_edge], Some([twin])) = (
+                half_edges.as_slice(),
+                directed.get(&directed_key(b, a)).map(Vec::as_slice),
+            ) {
+                twins[*half_edge as usize] = Some(*twin);
+            }
+        }
+
+        Self {
+            corners,
+            twins,
+            outgoing,
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.outgoing.len()
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.corners.len() / 3
+    }
+
+    pub fn half_edge_count(&self) -> usize {
+        self.corners.len()
+    }
+
+    /// The triangles as an index list, reflecting the edge flips.
+    pub fn indices(&self) -> &[u32] {
+        &self.corners
+    }
+
+    pub fn face_vertices(&self, face: u32) -> [u32; 3] {
+        let first = face as usize * 3;
+        [0, 1, 2].map(|k| self.corners[first + k])
+    }
+
+    /// Triangle on the left of `half_edge`.
+    pub fn face(&self, half_edge: u32) -> u32 {
+        half_edge / 3
+    }
+
+    pub fn origin(&self, half_edge: u32) -> u32 {
+        self.corners[half_edge as usize]
+    }
+
+    pub fn target(&self, half_edge: u32) -> u32 {
+        self.corners[self.next(half_edge) as usize]
+    }
+
+    /// Next half-edge around the same triangle.
+    pub fn next(&self, half_edge: u32) -> u32 {
+        next(half_edge)
+    }
+
+    /// Previous half-edge around the same triangle.
+    pub fn prev(&self, half_edge: u32) -> u32 {
+        next(next(half_edge))
+    }
+
+    /// Half-edge going the other way in the neighboring triangle.
+    pub fn twin(&self, half_edge: u32) -> Option<u32> {
+        self.twins[half_edge as usize]
+    }
+
+    pub fn is_boundary(&self, half_edge: u32) -> bool {
+        self.twin(half_edge).is_none()
+    }
+
+    /// Half-edges starting at `vertex`, in no particular order.
+    pub fn outgoing(&self, vertex: u32) -> &[u32] {
+        &self.outgoing[vertex as usize]
+    }
+
+    /// Whether `vertex` is on a boundary. Unused vertices are not.
+    pub fn is_boundary_vertex(&self, vertex: u32) -> bool {
+        self.outgoing(vertex)
+            .iter()
+            .any(|&half_edge| self.is_boundary(half_edge) || self.is_boundary(self.prev(half_edge)))
+    }
+
+    /// Half-edges starting at `vertex`, in fans of triangles connected by
+    /// their edges, each ordered counter-clockwise. A fan reaching a
+    /// boundary starts with its boundary half-edge. Manifold vertices have a
+    /// single fan.
+    pub fn vertex_fans(&self, vertex: u32) -> Vec<Vec<u32>> {
+        let outgoing = self.outgoing(vertex);
+        let mut visited = HashSet::new();
+        let mut fans = Vec::new();
+
+        // Fans starting on a boundary first, then the closed ones.
+        let starts = outgoing
+            .iter()
+            .filter(|&&half_edge| self.is_boundary(half_edge))
+            .chain(outgoing);
+        for &start in starts {
+            if visited.contains(&start) {
+                continue;
+            }
+            let mut fan = Vec::new();
+            let mut half_edge = Some(start);
+            while let Some(current) = half_edge.filter(|&current| visited.insert(current)) {
+                fan.push(current);
+                half_edge = self.twin(self.prev(current));
+            }
+            fans.push(fan);
+        }
+        fans
+    }
+
+    /// Vertices sharing an edge with `vertex`, counter-clockwise when the
+    /// vertex is manifold.
+    pub fn vertex_neighbors(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbors = Vec::new();
+        for fan in self.vertex_fans(vertex) {
+            for &half_edge in &fan {
+                neighbors.push(self.target(half_edge));
+            }
+            let last = self.prev(*fan.last().unwrap());
+            if self.is_boundary(last) {
+                neighbors.push(self.origin(last));
+            }
+        }
+        let mut seen = HashSet::new();
+        neighbors.retain(|&neighbor| neighbor != vertex && seen.insert(neighbor));
+        neighbors
+    }
+
+    /// Triangles using `vertex`, counter-clockwise when the vertex is
+    /// manifold.
+    pub fn vertex_faces(&self, vertex: u32) -> Vec<u32> {
+        let mut faces: Vec<u32> = self
+            .vertex_fans(vertex)
+            .into_iter()
+            .flatten()
+            .map(|half_edge| self.face(half_edge))
+            .collect();
+        let mut seen = HashSet::new();
+        faces.retain(|&face| seen.insert(face));
+        faces
+    }
+
+    /// Triangles across each edge of `face`, in the order of its corners.
+    pub fn face_neighbors(&self, face: u32) -> [Option<u32>; 3] {
+        [0, 1, 2].map(|k| self.twin(face * 3 + k).map(|twin| self.face(twin)))
+    }
+
+    /// One half-edge for each edge, the one with the lowest number.
+    pub fn edges(&self) -> impl Iterator<Item = u32> + '_ {
+        let mut boundaries = HashSet::new();
+        (0..self.corners.len() as u32).filter(move |&half_edge| match self.twin(half_edge) {
+            Some(twin) => half_edge < twin,
+            None => {
+                let (a, b) = (self.origin(half_edge), self.target(half_edge));
+                boundaries.insert(edge_key(a, b))
+            }
+        })
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges().count()
+    }
+
+    /// The boundaries, each as the vertices met going around it with the
+    /// surface on the left.
+    pub fn boundary_loops(&self) -> Vec<Vec<u32>> {
+        let mut visited = HashSet::new();
+        let mut loops = Vec::new();
+        for start in 0..self.corners.len() as u32 {
+            if !self.is_boundary(start) || visited.contains(&start) {
+                continue;
+            }
+            let mut boundary = Vec::new();
+            let mut half_edge = Some(start);
+            while let Some(current) = half_edge.filter(|&current| visited.insert(current)) {
+                boundary.push(self.origin(current));
+                half_edge = self
+                    .outgoing(self.target(current))
+                    .iter()
+                    .copied()
+                    .find(|&next| self.is_boundary(next) && !visited.contains(&next));
+            }
+            loops.push(boundary);
+        }
+        loops
+    }
+
+    /// Replaces the edge of `half_edge` with the other diagonal of the two
+    /// triangles sharing it. Returns false, and leaves the mesh unchanged,
+    /// when the edge is on a boundary or the other diagonal already exists.
+    ///
+    /// The two triangles keep their numbers and winding. Whether the flip
+    /// folds the surface is left to the caller.
+    pub fn flip_edge(&mut self, half_edge: u32) -> bool {
+        let Some(twin) = self.twin(half_edge) else {
+            return false;
+        };
+        let (a, b) = (self.origin(half_edge), self.target(half_edge));
+        let c = self.origin(self.prev(half_edge));
+        let d = self.origin(self.prev(twin));
+        if c == d || self.vertex_neighbors(c).contains(&d) {
+            return false;
+        }
+
+        // Triangles (a, b, c) and (b, a, d) become (a, d, c) and (d, b, c).
+        // Half-edges a->d and d->b were in the twin's triangle, b->c and
+        // c->a in this one, and keep their own twins.
+        let (first, second) = (self.face(half_edge) * 3, self.face(twin) * 3);
+        let kept = [
+            (self.next(twin), first),
+            (self.prev(twin), second),
+            (self.next(half_edge), second + 1),
+            (self.prev(half_edge), first + 2),
+        ]
+        .map(|(old, new)| (old, new, self.twin(old)));
+
+        for vertex in [a, b, c, d] {
+            self.outgoing[vertex as usize]
+                .retain(|&outgoing| outgoing / 3 != first / 3 && outgoing / 3 != second / 3);
+        }
+        for (start, corners) in [(first, [a, d, c]), (second, [d, b, c])] {
+            for (k, corner) in corners.into_iter().enumerate() {
+                self.corners[start as usize + k] = corner;
+                self.outgoing[corner as usize].push(start + k as u32);
+            }
+        }
+
+        for (_, new, twin) in kept {
+            self.twins[new as usize] = twin;
+            if let Some(twin) = twin {
+                self.twins[twin as usize] = Some(new);
+            }
+        }
+        self.twins[first as usize + 1] = Some(second + 2);
+        self.twins[second as usize + 2] = Some(first + 1);
+        true
+    }
+}
+
+/// Like [`edge_key`], but different for both directions of an edge.
+fn directed_key(from: u32, to: u32) -> u64 {
+    (from as u64) << 32 | to as u64
+}
+
+fn next(half_edge: u32) -> u32 {
+    if half_edge % 3 == 2 {
+        half_edge - 2
+    } else {
+        half_edge + 1
+    }
+}
+
+impl Mesh {
+    /// Connectivity of the triangles of the mesh.
+    pub fn half_edges(&self) -> HalfEdgeMesh {
+        HalfEdgeMesh::new(self.positions.len(), &self.indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::icosphere;
+
+    /// Two triangles sharing the edge 0-2 of the unit square.
+    fn square() -> HalfEdgeMesh {
+        HalfEdgeMesh::new(4, &[0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn connects_twins() {
+        let (positions, indices) = icosphere(1);
+        let mesh = HalfEdgeMesh::new(positions.len(), &indices);
+        for half_edge in 0..mesh.half_edge_count() as u32 {
+            let twin = mesh.twin(half_edge).unwrap();
+            assert_eq!(mesh.twin(twin), Some(half_edge));
+            assert_eq!(mesh.origin(twin), mesh.target(half_edge));
+        }
+        assert_eq!(mesh.edge_count(), 120);
+        assert!(mesh.boundary_loops().is_empty());
+        for vertex in 0..mesh.vertex_count() as u32 {
+            assert!(matches!(mesh.vertex_neighbors(vertex).len(), 5 | 6));
+        }
+    }
+
+    #[test]
+    fn walks_around_vertices() {
+        let mesh = square();
+        assert_eq!(mesh.vertex_neighbors(0), vec![1, 2, 3]);
+        assert_eq!(mesh.vertex_faces(0), vec![0, 1]);
+        assert_eq!(mesh.vertex_neighbors(1), vec![2, 0]);
+        assert_eq!(mesh.face_neighbors(0), [None, None, Some(1)]);
+        assert!(mesh.is_boundary_vertex(0));
+        assert_eq!(mesh.boundary_loops(), vec![vec![0, 1, 2, 3]]);
+        assert_eq!(mesh.edge_count(), 5);
+    }
+
+    #[test]
+    fn treats_non_manifold_edges_as_boundaries() {
+        let mesh = HalfEdgeMesh::new(5, &[0, 1, 2, 1, 0, 3, 0, 1, 4]);
+        assert!((0..9).all(|half_edge| mesh.twin(half_edge).is_none()));
+        assert_eq!(mesh.edge_count(), 7);
+    }
+
+    #[test]
+    fn flips_edges() {
+        let mut mesh = square();
+        assert!(mesh.flip_edge(2));
+        assert_eq!(mesh.indices(), &[2, 3, 1, 3, 0, 1]);
+        assert_eq!(mesh.twin(1), Some(5));
+        assert_eq!(mesh.edge_count(), 5);
+        assert_eq!(mesh.vertex_neighbors(0), vec![1, 3]);
+        assert_eq!(mesh.vertex_neighbors(1), vec![2, 3, 0]);
+        assert_eq!(mesh.boundary_loops(), vec![vec![2, 3, 0, 1]]);
+
+        // Flipping back restores the first diagonal.
+        assert!(mesh.flip_edge(1));
+        assert_eq!(mesh.edge_count(), 5);
+        assert!(mesh.vertex_neighbors(0).contains(&2));
+        assert!(!mesh.flip_edge(0));
+    }
+}
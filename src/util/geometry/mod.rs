@@ -0,0 +1,636 @@
+use cgmath::{prelude::*, Vector2, Vector3};
+use eframe::wgpu::{self, util::DeviceExt};
+use std::{collections::HashSet, f32::consts::PI};
+
+use crate::runner::Context;
+
+mod cleanup;
+mod error;
+mod gltf;
+mod half_edge;
+mod normals;
+mod obj;
+mod optimize;
+mod ply;
+mod primitives;
+mod simplify;
+mod stl;
+mod subdivide;
+mod uv;
+mod validate;
+
+pub use error::LoadError;
+pub use gltf::{
+    load_gltf, parse_gltf, save_glb, write_glb, AlphaMode, GltfMaterial, GltfMesh, GltfNode,
+    GltfPrimitive, GltfScene,
+};
+pub use half_edge::HalfEdgeMesh;
+pub use normals::NormalWeighting;
+pub use obj::{
+    load_obj, parse_mtl, parse_obj, save_obj, write_obj, ObjGroup, ObjMaterial, ObjModel,
+};
+pub use optimize::{
+    analyze_vertex_cache, optimize_overdraw, optimize_vertex_cache, optimize_vertex_fetch,
+    VertexCacheStats,
+};
+pub use ply::{load_ply, parse_ply, save_ply, write_ply, PlyFormat};
+pub use primitives::{arrow, capsule, cone, cuboid, cylinder, disc, plane, torus, uv_sphere};
+pub use simplify::simplify;
+pub use stl::{load_stl, parse_stl, save_stl, write_stl, StlFormat};
+pub use subdivide::subdivide_midpoint;
+pub use validate::{validate, ValidationReport};
+
+/// Triangle list with optional per-vertex attributes. Each attribute, when
+/// present, has one entry per position.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vector3<f32>>,
+    pub normals: Option<Vec<Vector3<f32>>>,
+    pub uvs: Option<Vec<Vector2<f32>>>,
+    pub tangents: Option<Vec<Vector3<f32>>>,
+    pub colors: Option<Vec<Vector3<f32>>>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vector3<f32>>, indices: Vec<u32>) -> Self {
+        Self {
+            positions,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn compute_normals(&mut self) -> &mut Self {
+        self.normals = Some(compute_normal_vectors(&mut self.positions, &self.indices));
+        self
+    }
+
+    /// One UV per vertex, so triangles crossing the seam stretch over the
+    /// whole texture. [`with_spherical_uvs`](Mesh::with_spherical_uvs) fixes
+    /// them at the cost of extra vertices.
+    pub fn compute_spherical_uvs(&mut self) -> &mut Self {
+        self.uvs = Some(
+            self.positions
+                .iter()
+                .map(|position| compute_spherical_uv(*position))
+                .collect(),
+        );
+        self
+    }
+
+    /// Tangents follow the UVs, so this does nothing on a mesh without UVs.
+    /// Normals are computed first if the mesh has none.
+    pub fn compute_tangents(&mut self) -> &mut Self {
+        let Some(uvs) = self.uvs.clone() else {
+            return self;
+        };
+        if self.normals.is_none() {
+            self.compute_normals();
+        }
+        let normals = self.normals.clone().unwrap();
+        self.tangents = Some(compute_tangent_vectors(
+            &mut self.positions,
+            uvs,
+            normals,
+            &self.indices,
+        ));
+        self
+    }
+
+    pub fn set_color(&mut self, color: Vector3<f32>) -> &mut Self {
+        self.colors = Some(vec![color; self.positions.len()]);
+        self
+    }
+
+    /// A mesh whose vertex `i` copies every attribute of vertex `sources[i]`
+    /// of this one, with new indices.
+    pub(crate) fn remapped(&self, sources: &[u32], indices: Vec<u32>) -> Mesh {
+        fn pick<T: Copy>(values: &[T], sources: &[u32]) -> Vec<T> {
+            sources
+                .iter()
+                .map(|&source| values[source as usize])
+                .collect()
+        }
+
+        Mesh {
+            positions: pick(&self.positions, sources),
+            normals: self
+                .normals
+                .as_deref()
+                .map(|normals| pick(normals, sources)),
+            uvs: self.uvs.as_deref().map(|uvs| pick(uvs, sources)),
+            tangents: self
+                .tangents
+                .as_deref()
+                .map(|tangents| pick(tangents, sources)),
+            colors: self.colors.as_deref().map(|colors| pick(colors, sources)),
+            indices,
+        }
+    }
+
+    /// Indices of the edges of the triangles, for a `LineList` topology.
+    pub fn line_list(&self) -> Vec<u32> {
+        compute_line_list(self.indices.clone())
+    }
+
+    /// Uploads the mesh as an interleaved vertex buffer and an index buffer.
+    ///
+    /// Attributes use fixed shader locations whether or not they are present:
+    /// position 0, normal 1, uv 2, tangent 3 and color 4.
+    pub fn to_gpu(&self, context: &Context) -> GpuMesh {
+        let mut attributes = vec![(0, wgpu::VertexFormat::Float32x3)];
+        if self.normals.is_some() {
+            attributes.push((1, wgpu::VertexFormat::Float32x3));
+        }
+        if self.uvs.is_some() {
+            attributes.push((2, wgpu::VertexFormat::Float32x2));
+        }
+        if self.tangents.is_some() {
+            attributes.push((3, wgpu::VertexFormat::Float32x3));
+        }
+        if self.colors.is_some() {
+            attributes.push((4, wgpu::VertexFormat::Float32x3));
+        }
+
+        let mut offset = 0;
+        let attributes: Vec<wgpu::VertexAttribute> = attributes
+            .into_iter()
+            .map(|(shader_location, format)| {
+                let attribute = wgpu::VertexAttribute {
+                    offset,
+                    shader_location,
+                    format,
+                };
+                offset += format.size();
+                attribute
+            })
+            .collect();
+        let array_stride = offset;
+
+        let mut vertices: Vec<f32> =
+            Vec::with_capacity(self.positions.len() * array_stride as usize / 4);
+        for (i, position) in self.positions.iter().enumerate() {
+            vertices.extend_from_slice(&[position.x, position.y, position.z]);
+            if let Some(normals) = &self.normals {
+                vertices.extend_from_slice(&[normals[i].x, normals[i].y, normals[i].z]);
+            }
+            if let Some(uvs) = &self.uvs {
+                vertices.extend_from_slice(&[uvs[i].x, uvs[i].y]);
+            }
+            if let Some(tangents) = &self.tangents {
+                vertices.extend_from_slice(&[tangents[i].x, tangents[i].y, tangents[i].z]);
+            }
+            if let Some(colors) = &self.colors {
+                vertices.extend_from_slice(&[colors[i].x, colors[i].y, colors[i].z]);
+            }
+        }
+
+        let vertex_buffer =
+            context
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Mesh Vertex Buffer"),
+                    contents: bytemuck::cast_slice(vertices.as_slice()),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        GpuMesh {
+            vertex_buffer,
+            index_buffer: IndexBuffer::new(context, &self.indices),
+            array_stride,
+            attributes,
+        }
+    }
+}
+
+impl From<(Vec<Vector3<f32>>, Vec<u32>)> for Mesh {
+    fn from((positions, indices): (Vec<Vector3<f32>>, Vec<u32>)) -> Self {
+        Self::new(positions, indices)
+    }
+}
+
+/// Buffers created by [`Mesh::to_gpu`].
+pub struct GpuMesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: IndexBuffer,
+    array_stride: wgpu::BufferAddress,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl GpuMesh {
+    pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.vertex_buffer
+    }
+
+    pub fn index_buffer(&self) -> &wgpu::Buffer {
+        self.index_buffer.buffer()
+    }
+
+    pub fn num_indices(&self) -> u32 {
+        self.index_buffer.num_indices()
+    }
+
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        self.index_buffer.format()
+    }
+
+    pub fn desc(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &self.attributes,
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        self.index_buffer.bind(render_pass);
+        render_pass.draw_indexed(0..self.num_indices(), 0, 0..1);
+    }
+}
+
+/// `Uint16` when every index fits in 16 bits, which halves the size of the
+/// index buffer, and `Uint32` otherwise. `0xFFFF` is left out since strips use
+/// it to restart.
+pub fn index_format(indices: &[u32]) -> wgpu::IndexFormat {
+    if indices.iter().all(|&index| index < u16::MAX as u32) {
+        wgpu::IndexFormat::Uint16
+    } else {
+        wgpu::IndexFormat::Uint32
+    }
+}
+
+/// Index buffer in the format picked by [`index_format`].
+pub struct IndexBuffer {
+    buffer: wgpu::Buffer,
+    format: wgpu::IndexFormat,
+    num_indices: u32,
+}
+
+impl IndexBuffer {
+    pub fn new(context: &Context, indices: &[u32]) -> Self {
+        let format = index_format(indices);
+        let narrow: Vec<u16>;
+        let contents = match format {
+            wgpu::IndexFormat::Uint16 => {
+                narrow = indices.iter().map(|&index| index as u16).collect();
+                bytemuck::cast_slice(narrow.as_slice())
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices),
+        };
+        let buffer = context
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Index Buffer"),
+                contents,
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        Self {
+            buffer,
+            format,
+            num_indices: indices.len() as u32,
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        self.format
+    }
+
+    pub fn num_indices(&self) -> u32 {
+        self.num_indices
+    }
+
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_index_buffer(self.buffer.slice(..), self.format);
+    }
+}
+
+pub fn icosphere(order: u32) -> (Vec<Vector3<f32>>, Vec<u32>) {
+    let f = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions = vec![
+        cgmath::Vector3 {
+            x: -1.0,
+            y: f,
+            z: 0.0,
+        },
+        cgmath::Vector3 {
+            x: 1.0,
+            y: f,
+            z: 0.0,
+        },
+        cgmath::Vector3 {
+            x: -1.0,
+            y: -f,
+            z: 0.0,
+        },
+        cgmath::Vector3 {
+            x: 1.0,
+            y: -f,
+            z: 0.0,
+        },
+        cgmath::Vector3 {
+            x: 0.0,
+            y: -1.0,
+            z: f,
+        },
+        cgmath::Vector3 {
+            x: 0.0,
+            y: 1.0,
+            z: f,
+        },
+        cgmath::Vector3 {
+            x: 0.0,
+            y: -1.0,
+            z: -f,
+        },
+        cgmath::Vector3 {
+            x: 0.0,
+            y: 1.0,
+            z: -f,
+        },
+        cgmath::Vector3 {
+            x: f,
+            y: 0.0,
+            z: -1.0,
+        },
+        cgmath::Vector3 {
+            x: f,
+            y: 0.0,
+            z: 1.0,
+        },
+        cgmath::Vector3 {
+            x: -f,
+            y: 0.0,
+            z: -1.0,
+        },
+        cgmath::Vector3 {
+            x: -f,
+            y: 0.0,
+            z: 1.,
+        },
+    ];
+
+    let mut indices: Vec<u32> = vec![
+        0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11, 11, 10, 2, 5, 11, 4, 1, 5, 9, 7, 1, 8, 10,
+        7, 6, 3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9, 9, 8, 1, 4, 9, 5, 2, 4, 11, 6, 2, 10, 8,
+        6, 7,
+    ];
+
+    for _ in 0..order {
+        (positions, indices) = subdivide_midpoint(&positions, &indices);
+    }
+
+    let positions: Vec<Vector3<f32>> = positions
+        .iter()
+        .map(|position| position.normalize())
+        .collect();
+    let indices = optimize_vertex_cache(&indices, positions.len());
+
+    (positions, indices)
+}
+
+pub fn compute_spherical_uv(position: Vector3<f32>) -> Vector2<f32> {
+    let u = (position.x.atan2(position.z) + PI) / PI;
+    let v = (position.y / position.magnitude()).acos() / PI;
+
+    Vector2 { x: u, y: v }
+}
+
+/// Unit normal of a counter-clockwise triangle, or zero when the triangle
+/// is degenerate.
+pub fn compute_triangle_normal(
+    v1: &Vector3<f32>,
+    v2: &Vector3<f32>,
+    v3: &Vector3<f32>,
+) -> Vector3<f32> {
+    let position_1_2 = v2 - v1;
+    let position_1_3 = v3 - v1;
+
+    normalize_or_zero(position_1_2.cross(position_1_3))
+}
+
+pub fn compute_triangle_tangent(
+    pos1: &Vector3<f32>,
+    uv1: &Vector2<f32>,
+    pos2: &Vector3<f32>,
+    uv2: &Vector2<f32>,
+    pos3: &Vector3<f32>,
+    uv3: &Vector2<f32>,
+) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+    let position_1_2 = pos2 - pos1;
+    let position_1_3 = pos3 - pos1;
+
+    let tex_coords_1_2 = uv2 - uv1;
+    let tex_coords_1_3 = uv3 - uv1;
+
+    let tangent =
+        normalize_or_zero(tex_coords_1_3.y * position_1_2 - tex_coords_1_2.y * position_1_3);
+    let bitangent =
+        normalize_or_zero(-tex_coords_1_3.x * position_1_2 + tex_coords_1_2.x * position_1_3);
+
+    (tangent, bitangent)
+}
+
+pub fn compute_normal_vectors(
+    positions: &mut [Vector3<f32>],
+    indices: &Vec<u32>,
+) -> Vec<Vector3<f32>> {
+    let mut normals: Vec<cgmath::Vector3<f32>> = positions
+        .iter()
+        .map(|_| cgmath::Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        })
+        .collect();
+
+    indices
+        .as_slice()
+        .chunks_exact(3)
+        .map(|item| (item[0] as usize, item[1] as usize, item[2] as usize))
+        .for_each(|indices| {
+            let normal = compute_triangle_normal(
+                &positions[indices.0],
+                &positions[indices.1],
+                &positions[indices.2],
+            );
+
+            normals[indices.0] += normal;
+            normals[indices.1] += normal;
+            normals[indices.2] += normal;
+        });
+
+    normals.iter().map(|n| normalize_or_zero(*n)).collect()
+}
+
+pub fn compute_tangent_vectors(
+    positions: &mut [Vector3<f32>],
+    uvs: Vec<Vector2<f32>>,
+    normals: Vec<Vector3<f32>>,
+    indices: &Vec<u32>,
+) -> Vec<Vector3<f32>> {
+    let mut tangents: Vec<Vector3<f32>> = positions
+        .iter()
+        .map(|_| cgmath::Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        })
+        .collect();
+
+    indices
+        .as_slice()
+        .chunks_exact(3)
+        .map(|item| (item[0] as usize, item[1] as usize, item[2] as usize))
+        .for_each(|indices| {
+            let (tangent, _) = compute_triangle_tangent(
+                &positions[indices.0],
+                &uvs[indices.0],
+                &positions[indices.1],
+                &uvs[indices.1],
+                &positions[indices.2],
+                &uvs[indices.2],
+            );
+
+            tangents[indices.0] += tangent;
+            tangents[indices.1] += tangent;
+            tangents[indices.2] += tangent;
+        });
+
+    (0..tangents.len())
+        .map(|i| {
+            let tangent = normalize_or_zero(tangents[i]);
+            let tangent = tangent - normals[i].dot(tangent) * normals[i];
+            normalize_or_zero(tangent)
+        })
+        .collect()
+}
+
+/// Key of the edge between two vertices, the same for both directions. Any
+/// two indices give a different key, unlike pairing functions on `u32`,
+/// which overflow past about 46000 vertices.
+pub(crate) fn edge_key(a: u32, b: u32) -> u64 {
+    (a.min(b) as u64) << 32 | a.max(b) as u64
+}
+
+/// Degenerate triangles and vertices used only by them would otherwise get
+/// NaN normals, which spread through the shading.
+pub(crate) fn normalize_or_zero(vector: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude2() > 0.0 {
+        vector.normalize()
+    } else {
+        Vector3::zero()
+    }
+}
+
+/// Each edge of the triangles once, as a line list, in the order the
+/// triangles use them.
+pub fn compute_line_list(triangle_list: Vec<u32>) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+    for triangle in triangle_list.chunks_exact(3) {
+        for k in 0..3 {
+            let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+            if seen.insert(edge_key(a, b)) {
+                lines.extend_from_slice(&[a, b]);
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_smallest_index_format() {
+        assert_eq!(index_format(&[0, 1, 2]), wgpu::IndexFormat::Uint16);
+        assert_eq!(index_format(&[0, 1, 65534]), wgpu::IndexFormat::Uint16);
+        // 0xFFFF restarts strips, keep it out of 16-bit buffers.
+        assert_eq!(index_format(&[0, 1, 65535]), wgpu::IndexFormat::Uint32);
+        assert_eq!(index_format(&[0, 1, 70000]), wgpu::IndexFormat::Uint32);
+
+        // 255 x 255 and 257 x 257 vertices.
+        let mesh = plane(1.0, 1.0, 254, 254);
+        assert!(mesh.vertex_count() < 65535);
+        assert_eq!(index_format(&mesh.indices), wgpu::IndexFormat::Uint16);
+        let mesh = plane(1.0, 1.0, 256, 256);
+        assert!(mesh.vertex_count() > 65535);
+        assert_eq!(index_format(&mesh.indices), wgpu::IndexFormat::Uint32);
+    }
+
+    #[test]
+    fn tangents_need_uvs() {
+        let (positions, indices) = icosphere(1);
+        let mut mesh = Mesh::new(positions, indices);
+        mesh.compute_tangents();
+        assert!(mesh.tangents.is_none());
+        assert!(mesh.normals.is_none());
+
+        mesh.compute_spherical_uvs().compute_tangents();
+        assert!(mesh.tangents.is_some());
+    }
+
+    #[test]
+    fn gpu_layout_follows_the_attributes() {
+        let Ok(runner) = crate::runner::RunnerBuilder::new().build_headless() else {
+            eprintln!("no WGPU adapter available, skipping");
+            return;
+        };
+        let context = runner.context();
+        let layout = |mesh: &Mesh| {
+            let gpu_mesh = mesh.to_gpu(&context);
+            let desc = gpu_mesh.desc();
+            let attributes: Vec<(u32, u64)> = desc
+                .attributes
+                .iter()
+                .map(|attribute| (attribute.shader_location, attribute.offset))
+                .collect();
+            assert_eq!(
+                gpu_mesh.vertex_buffer().size(),
+                desc.array_stride * mesh.vertex_count() as u64
+            );
+            (attributes, desc.array_stride)
+        };
+
+        let mut mesh = uv_sphere(1.0, 8, 4);
+        mesh.set_color(Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            layout(&mesh),
+            (vec![(0, 0), (1, 12), (2, 24), (3, 32), (4, 44)], 56)
+        );
+
+        mesh.uvs = None;
+        mesh.tangents = None;
+        assert_eq!(layout(&mesh), (vec![(0, 0), (1, 12), (4, 24)], 36));
+
+        let mesh = Mesh::new(mesh.positions, mesh.indices);
+        assert_eq!(layout(&mesh), (vec![(0, 0)], 12));
+    }
+
+    #[test]
+    fn line_list_with_large_indices() {
+        let mut lines = compute_line_list(vec![70000, 70001, 100000]);
+        for line in lines.chunks_exact_mut(2) {
+            line.sort_unstable();
+        }
+        let mut lines: Vec<&[u32]> = lines.chunks_exact(2).collect();
+        lines.sort_unstable();
+        assert_eq!(lines, [[70000, 70001], [70000, 100000], [70001, 100000]]);
+    }
+}
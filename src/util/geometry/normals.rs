@@ -0,0 +1,262 @@
+use cgmath::{prelude::*, Vector3};
+
+use super::{cleanup::position_groups, normalize_or_zero, HalfEdgeMesh, Mesh};
+
+/// How the normals of the faces around a vertex are averaged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NormalWeighting {
+    /// Every face counts the same, like [`compute_normal_vectors`](super::compute_normal_vectors).
+    #[default]
+    Uniform,
+    /// Faces count in proportion to their area.
+    Area,
+    /// Faces count in proportion to their angle at the vertex, which does
+    /// not depend on how the surface is triangulated.
+    Angle,
+}
+
+impl Mesh {
+    /// A copy of the mesh with normals that are smooth across edges whose
+    /// faces meet at less than `crease_angle` (in radians) and sharp across
+    /// the others. Vertices on sharp edges are split, so the copy is
+    /// re-indexed. Other attributes are carried over and tangents, if any,
+    /// are recomputed.
+    ///
+    /// Vertices at the same position, like the copies of a vertex on a UV
+    /// seam, are smoothed together and get the same normal, while keeping
+    /// their own UVs. A small tolerance keeps coplanar faces together, so
+    /// an angle of 0 gives flat normals and an angle of `PI` smooths
+    /// everything.
+    pub fn with_smooth_normals(&self, crease_angle: f32, weighting: NormalWeighting) -> Mesh {
+        let min_cos = crease_angle.cos() - 1e-4;
+
+        let faces: Vec<[u32; 3]> = self
+            .indices
+            .chunks_exact(3)
+            .map(|face| [face[0], face[1], face[2]])
+            .collect();
+        let normals: Vec<Vector3<f32>> = faces
+            .iter()
+            .map(|&[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|index| self.positions[index as usize]);
+                (b - a).cross(c - a)
+            })
+            .collect();
+        let unit = |face: usize| normalize_or_zero(normals[face]);
+
+        // Faces are connected through the first vertex at each position, so
+        // that seams do not cut the surface.
+        let groups = position_groups(&self.positions);
+        let welded_indices: Vec<u32> = self
+            .indices
+            .iter()
+            .map(|&index| groups[index as usize])
+            .collect();
+        let half_edges = HalfEdgeMesh::new(self.positions.len(), &welded_indices);
+
+        let mut sources = Vec::new();
+        let mut smooth_normals = Vec::new();
+        // The entry of `smooth_normals` of each new vertex.
+        let mut vertex_normals = Vec::new();
+        let mut indices = self.indices.clone();
+
+        for position in 0..self.positions.len() as u32 {
+            // Corners of the faces around the position, as the half-edges
+            // leaving it, end up in the same group when their faces share
+            // an edge and meet at less than the crease angle.
+            let corners = half_edges.outgoing(position);
+            let mut group: Vec<usize> = (0..corners.len()).collect();
+            fn root(group: &mut [usize], mut i: usize) -> usize {
+                while group[i] != i {
+                    group[i] = group[group[i]];
+                    i = group[i];
+                }
+                i
+            }
+            for (i, &corner) in corners.iter().enumerate() {
+                let Some(twin) = half_edges.twin(corner) else {
+                    continue;
+                };
+                let (f, g) = (corner as usize / 3, twin as usize / 3);
+                let j = corners.iter().position(|&other| other as usize / 3 == g);
+                if let Some(j) = j.filter(|_| unit(f).dot(unit(g)) >= min_cos) {
+                    let (a, b) = (root(&mut group, i), root(&mut group, j));
+                    group[b] = a;
+                }
+            }
+
+            // Each group gets a normal, shared by the vertices of the group
+            // at this position.
+            let mut group_normals: Vec<(usize, usize)> = Vec::new();
+            let mut new_vertices: Vec<(usize, u32, u32)> = Vec::new();
+            for (i, &corner) in corners.iter().enumerate() {
+                let face = corner as usize / 3;
+                let vertex = self.indices[corner as usize];
+                let group_root = root(&mut group, i);
+                let normal = match group_normals.iter().find(|(r, _)| *r == group_root) {
+                    Some(&(_, normal)) => normal,
+                    None => {
+                        smooth_normals.push(Vector3::zero());
+                        group_normals.push((group_root, smooth_normals.len() - 1));
+                        smooth_normals.len() - 1
+                    }
+                };
+                let new_vertex = match new_vertices
+                    .iter()
+                    .find(|&&(r, v, _)| r == group_root && v == vertex)
+                {
+                    Some(&(_, _, new_vertex)) => new_vertex,
+                    None => {
+                        let new_vertex = sources.len() as u32;
+                        sources.push(vertex);
+                        vertex_normals.push(normal);
+                        new_vertices.push((group_root, vertex, new_vertex));
+                        new_vertex
+                    }
+                };
+
+                smooth_normals[normal] += match weighting {
+                    NormalWeighting::Uniform => unit(face),
+                    NormalWeighting::Area => normals[face],
+                    NormalWeighting::Angle => unit(face) * self.corner_angle(faces[face], vertex),
+                };
+                indices[corner as usize] = new_vertex;
+            }
+        }
+
+        let mut mesh = self.remapped(&sources, indices);
+        mesh.normals = Some(
+            vertex_normals
+                .into_iter()
+                .map(|normal| normalize_or_zero(smooth_normals[normal]))
+                .collect(),
+        );
+        if mesh.tangents.is_some() && mesh.uvs.is_some() {
+            mesh.compute_tangents();
+        }
+        mesh
+    }
+
+    /// A copy of the mesh where each flat region has its own vertices and
+    /// the normal of its faces.
+    pub fn with_flat_normals(&self) -> Mesh {
+        self.with_smooth_normals(0.0, NormalWeighting::Uniform)
+    }
+
+    /// Angle of `face` at its corner `vertex`.
+    fn corner_angle(&self, face: [u32; 3], vertex: u32) -> f32 {
+        let corner = face.iter().position(|&index| index == vertex).unwrap();
+        let [a, b, c] = [0, 1, 2].map(|k| self.positions[face[(corner + k) % 3] as usize]);
+        let (ab, ac) = (b - a, c - a);
+        if ab.magnitude2() == 0.0 || ac.magnitude2() == 0.0 {
+            return 0.0;
+        }
+        ab.angle(ac).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// A unit cube with 8 shared corners.
+    fn shared_cube() -> Mesh {
+        let positions = (0..8)
+            .map(|i| {
+                Vector3::new(
+                    (i & 1) as f32 - 0.5,
+                    ((i >> 1) & 1) as f32 - 0.5,
+                    ((i >> 2) & 1) as f32 - 0.5,
+                )
+            })
+            .collect();
+        let indices = vec![
+            0, 2, 3, 0, 3, 1, // -z
+            4, 5, 7, 4, 7, 6, // +z
+            0, 4, 6, 0, 6, 2, // -x
+            1, 3, 7, 1, 7, 5, // +x
+            0, 1, 5, 0, 5, 4, // -y
+            2, 6, 7, 2, 7, 3, // +y
+        ];
+        Mesh::new(positions, indices)
+    }
+
+    #[test]
+    fn splits_hard_edges() {
+        let cube = shared_cube().with_smooth_normals(PI / 6.0, NormalWeighting::Uniform);
+        assert_eq!(cube.vertex_count(), 24);
+        assert_eq!(cube.triangle_count(), 12);
+
+        let normals = cube.normals.as_ref().unwrap();
+        for triangle in cube.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| cube.positions[triangle[k] as usize]);
+            let face = (b - a).cross(c - a).normalize();
+            for &index in triangle {
+                assert!((normals[index as usize] - face).magnitude() < 1e-6);
+            }
+        }
+        assert_eq!(shared_cube().with_flat_normals().vertex_count(), 24);
+    }
+
+    #[test]
+    fn keeps_smooth_edges() {
+        let cube = shared_cube().with_smooth_normals(PI, NormalWeighting::Angle);
+        assert_eq!(cube.vertex_count(), 8);
+        for (position, normal) in cube.positions.iter().zip(cube.normals.unwrap()) {
+            assert!((position.normalize() - normal).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn angle_weighting_ignores_triangulation() {
+        // Corner 2 has two triangles on the +y face and one on each of the
+        // others, so only angle weighting points exactly along the diagonal.
+        let cube = shared_cube();
+        let uniform = cube.with_smooth_normals(PI, NormalWeighting::Uniform);
+        let angle = cube.with_smooth_normals(PI, NormalWeighting::Angle);
+        let diagonal = Vector3::new(-1.0, 1.0, -1.0).normalize();
+
+        assert!((uniform.normals.unwrap()[2] - diagonal).magnitude() > 1e-3);
+        assert!((angle.normals.unwrap()[2] - diagonal).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn area_weighting_favors_large_faces() {
+        // A large and a small triangle folded along the x axis.
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -10.0),
+            Vector3::new(0.0, 0.1, 0.0),
+        ];
+        let mesh = Mesh::new(positions, vec![0, 1, 2, 0, 3, 1]);
+        let uniform = mesh.with_smooth_normals(PI, NormalWeighting::Uniform);
+        let area = mesh.with_smooth_normals(PI, NormalWeighting::Area);
+
+        let up = Vector3::unit_y();
+        let uniform_normal = uniform.normals.unwrap()[0];
+        let area_normal = area.normals.unwrap()[0];
+        assert!(area_normal.dot(up) > uniform_normal.dot(up));
+    }
+
+    #[test]
+    fn smooths_across_uv_seams() {
+        // The first and last meridians of the sphere, and the vertices of
+        // each pole, are copies with their own UVs.
+        let sphere = crate::util::geometry::uv_sphere(1.0, 16, 8);
+        let smooth = sphere.with_smooth_normals(PI / 3.0, NormalWeighting::Angle);
+        // No split, but the unused copies at the poles are left out.
+        assert!(smooth.vertex_count() <= sphere.vertex_count());
+
+        let normals = smooth.normals.as_ref().unwrap();
+        for (a, b) in (0..smooth.vertex_count()).flat_map(|a| (0..a).map(move |b| (a, b))) {
+            if (smooth.positions[a] - smooth.positions[b]).magnitude() < 1e-5 {
+                assert!((normals[a] - normals[b]).magnitude() < 1e-5);
+            }
+        }
+        for (position, normal) in smooth.positions.iter().zip(normals) {
+            assert!((position.normalize() - normal).magnitude() < 0.05);
+        }
+    }
+}
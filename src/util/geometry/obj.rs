@@ -0,0 +1,598 @@
+use cgmath::{Vector2, Vector3};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use super::{LoadError, Mesh};
+
+/// A Wavefront OBJ file: one mesh with every face, split in groups that
+/// each use a range of its indices.
+#[derive(Debug, Clone, Default)]
+pub struct ObjModel {
+    pub mesh: Mesh,
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    /// The material used by `group`, if it names one that was loaded.
+    pub fn material(&self, group: &ObjGroup) -> Option<&ObjMaterial> {
+        let name = group.material.as_ref()?;
+        self.materials
+            .iter()
+            .find(|material| &material.name == name)
+    }
+}
+
+/// Consecutive faces sharing a group name (`g` or `o`) and a material
+/// (`usemtl`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    /// Range of `mesh.indices`, to use with `draw_indexed`.
+    pub indices: Range<u32>,
+}
+
+/// A material from an MTL file. Texture paths are relative to the MTL
+/// file, except with [`load_obj`] which resolves them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Ka`
+    pub ambient: Vector3<f32>,
+    /// `Kd`
+    pub diffuse: Vector3<f32>,
+    /// `Ks`
+    pub specular: Vector3<f32>,
+    /// `Ke`
+    pub emissive: Vector3<f32>,
+    /// `Ns`
+    pub shininess: f32,
+    /// `d`, or `1 - Tr`
+    pub opacity: f32,
+    /// `map_Kd`
+    pub diffuse_texture: Option<PathBuf>,
+    /// `map_Ks`
+    pub specular_texture: Option<PathBuf>,
+    /// `map_Bump`, `bump` or `norm`
+    pub normal_texture: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ambient: Vector3::new(0.0, 0.0, 0.0),
+            diffuse: Vector3::new(0.8, 0.8, 0.8),
+            specular: Vector3::new(0.0, 0.0, 0.0),
+            emissive: Vector3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+/// Reads an OBJ file and the MTL files it references, looked up next to it.
+/// A missing MTL file is logged and its materials left out.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ObjModel, LoadError> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let source = std::fs::read_to_string(path)?;
+    let mut model = parse_obj(&source)?;
+
+    for library in mtl_libraries(&source) {
+        let mtl_path = dir.join(library);
+        let mtl_source = match std::fs::read_to_string(&mtl_path) {
+            Ok(mtl_source) => mtl_source,
+            Err(error) => {
+                log::warn!("Failed to read {}: {error}", mtl_path.display());
+                continue;
+            }
+        };
+
+        let mtl_dir = mtl_path.parent().unwrap_or(Path::new(""));
+        for mut material in parse_mtl(&mtl_source)? {
+            for texture in [
+                &mut material.diffuse_texture,
+                &mut material.specular_texture,
+                &mut material.normal_texture,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *texture = mtl_dir.join(&texture);
+            }
+            model.materials.push(material);
+        }
+    }
+
+    Ok(model)
+}
+
+/// Parses the content of an OBJ file, without its materials.
+///
+/// Polygons are split in triangle fans, so they should be convex. Each
+/// distinct `v/vt/vn` triple becomes one vertex. UVs are flipped vertically
+/// to match WGPU texture coordinates. Normals are computed when some faces
+/// have none.
+pub fn parse_obj(source: &str) -> Result<ObjModel, LoadError> {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut mesh = Mesh::default();
+    let mut mesh_colors = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut has_colors = false;
+    let mut has_uvs = false;
+    let mut all_normals = true;
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+    let mut groups: Vec<ObjGroup> = Vec::new();
+    let mut name = String::new();
+    let mut material: Option<String> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let number = Some(number + 1);
+        let mut tokens = without_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let values = parse_floats(&rest, number)?;
+                if values.len() < 3 {
+                    return Err(LoadError::parse(number, "a vertex needs 3 coordinates"));
+                }
+                positions.push(Vector3::new(values[0], values[1], values[2]));
+                // Vertex colors are a common extension: `v x y z r g b`.
+                if values.len() >= 6 {
+                    has_colors = true;
+                    colors.push(Vector3::new(values[3], values[4], values[5]));
+                } else {
+                    colors.push(Vector3::new(1.0, 1.0, 1.0));
+                }
+            }
+            "vt" => {
+                let values = parse_floats(&rest, number)?;
+                if values.is_empty() {
+                    return Err(LoadError::parse(number, "a UV needs coordinates"));
+                }
+                let v = values.get(1).copied().unwrap_or(0.0);
+                uvs.push(Vector2::new(values[0], 1.0 - v));
+            }
+            "vn" => {
+                let values = parse_floats(&rest, number)?;
+                if values.len() < 3 {
+                    return Err(LoadError::parse(number, "a normal needs 3 coordinates"));
+                }
+                normals.push(Vector3::new(values[0], values[1], values[2]));
+            }
+            "f" => {
+                if rest.len() < 3 {
+                    return Err(LoadError::parse(number, "a face needs at least 3 vertices"));
+                }
+
+                let mut corners = Vec::with_capacity(rest.len());
+                for corner in &rest {
+                    let mut parts = corner.split('/');
+                    let v =
+                        parse_index(parts.next(), positions.len(), number)?.ok_or_else(|| {
+                            LoadError::parse(number, "a face vertex needs a position")
+                        })?;
+                    let vt = parse_index(parts.next(), uvs.len(), number)?;
+                    let vn = parse_index(parts.next(), normals.len(), number)?;
+
+                    let index = *vertices.entry((v, vt, vn)).or_insert_with(|| {
+                        mesh.positions.push(positions[v]);
+                        mesh_colors.push(colors[v]);
+                        mesh_uvs.push(vt.map_or(Vector2::new(0.0, 0.0), |vt| uvs[vt]));
+                        let normal = vn.map_or(Vector3::new(0.0, 0.0, 0.0), |vn| normals[vn]);
+                        mesh_normals.push(normal);
+                        mesh.positions.len() as u32 - 1
+                    });
+                    has_uvs |= vt.is_some();
+                    all_normals &= vn.is_some();
+                    corners.push(index);
+                }
+
+                let start = mesh.indices.len() as u32;
+                for k in 1..corners.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[corners[0], corners[k], corners[k + 1]]);
+                }
+
+                match groups.last_mut() {
+                    Some(group)
+                        if group.indices.end == start
+                            && group.name == name
+                            && group.material == material =>
+                    {
+                        group.indices.end = mesh.indices.len() as u32;
+                    }
+                    _ => groups.push(ObjGroup {
+                        name: name.clone(),
+                        material: material.clone(),
+                        indices: start..mesh.indices.len() as u32,
+                    }),
+                }
+            }
+            "g" | "o" => name = rest.join(" "),
+            "usemtl" => material = Some(rest.join(" ")),
+            // Materials are read by `load_obj`, smoothing groups, lines,
+            // points and curves are not supported.
+            _ => {}
+        }
+    }
+
+    if has_colors {
+        mesh.colors = Some(mesh_colors);
+    }
+    if has_uvs {
+        mesh.uvs = Some(mesh_uvs);
+    }
+    if all_normals && !mesh.positions.is_empty() {
+        mesh.normals = Some(mesh_normals);
+    } else {
+        mesh.compute_normals();
+    }
+
+    Ok(ObjModel {
+        mesh,
+        groups,
+        materials: Vec::new(),
+    })
+}
+
+/// The part of a line before its `#` comment, if any.
+fn without_comment(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(data, _)| data)
+}
+
+/// Parses the content of an MTL file.
+pub fn parse_mtl(source: &str) -> Result<Vec<ObjMaterial>, LoadError> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let number = Some(number + 1);
+        let mut tokens = without_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(&rest.join(" ")));
+            continue;
+        }
+        let Some(material) = materials.last_mut() else {
+            return Err(LoadError::parse(
+                number,
+                "material property before `newmtl`",
+            ));
+        };
+
+        let color = |rest: &[&str]| -> Result<Vector3<f32>, LoadError> {
+            let values = parse_floats(rest, number)?;
+            match values.as_slice() {
+                [gray] => Ok(Vector3::new(*gray, *gray, *gray)),
+                [r, g, b, ..] => Ok(Vector3::new(*r, *g, *b)),
+                _ => Err(LoadError::parse(number, "a color needs 1 or 3 values")),
+            }
+        };
+        let scalar = |rest: &[&str]| -> Result<f32, LoadError> {
+            parse_floats(rest, number)?
+                .first()
+                .copied()
+                .ok_or_else(|| LoadError::parse(number, "missing value"))
+        };
+        // Texture options like `-bm 1` come first, the file name last.
+        let texture = |rest: &[&str]| rest.last().map(PathBuf::from);
+
+        match keyword {
+            "Ka" => material.ambient = color(&rest)?,
+            "Kd" => material.diffuse = color(&rest)?,
+            "Ks" => material.specular = color(&rest)?,
+            "Ke" => material.emissive = color(&rest)?,
+            "Ns" => material.shininess = scalar(&rest)?,
+            "d" => material.opacity = scalar(&rest)?,
+            "Tr" => material.opacity = 1.0 - scalar(&rest)?,
+            "map_Kd" => material.diffuse_texture = texture(&rest),
+            "map_Ks" => material.specular_texture = texture(&rest),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = texture(&rest),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+pub fn save_obj(mesh: &Mesh, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_obj(mesh, &mut writer)?;
+    writer.flush()
+}
+
+/// Writes `mesh` as an OBJ file, with its normals and UVs when it has some
+/// and its colors as the `v x y z r g b` extension. OBJ cannot store
+/// tangents, they are left out.
+pub fn write_obj(mesh: &Mesh, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, "# wgpu-bootstrap")?;
+    for (i, position) in mesh.positions.iter().enumerate() {
+        write!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+        if let Some(colors) = &mesh.colors {
+            write!(writer, " {} {} {}", colors[i].x, colors[i].y, colors[i].z)?;
+        }
+        writeln!(writer)?;
+    }
+    if let Some(uvs) = &mesh.uvs {
+        for uv in uvs {
+            writeln!(writer, "vt {} {}", uv.x, 1.0 - uv.y)?;
+        }
+    }
+    if let Some(normals) = &mesh.normals {
+        for normal in normals {
+            writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+    }
+
+    // Every attribute has the same index as the position.
+    let corner = |index: u32| {
+        let index = index + 1;
+        match (mesh.uvs.is_some(), mesh.normals.is_some()) {
+            (false, false) => format!("{index}"),
+            (true, false) => format!("{index}/{index}"),
+            (false, true) => format!("{index}//{index}"),
+            (true, true) => format!("{index}/{index}/{index}"),
+        }
+    };
+    for triangle in mesh.indices.chunks_exact(3) {
+        writeln!(
+            writer,
+            "f {} {} {}",
+            corner(triangle[0]),
+            corner(triangle[1]),
+            corner(triangle[2])
+        )?;
+    }
+    Ok(())
+}
+
+fn mtl_libraries(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = without_comment(line).split_whitespace();
+            (tokens.next() == Some("mtllib")).then_some(tokens)
+        })
+        .flatten()
+        .collect()
+}
+
+fn parse_floats(tokens: &[&str], line: Option<usize>) -> Result<Vec<f32>, LoadError> {
+    tokens
+        .iter()
+        .map(|token| {
+            token
+                .parse()
+                .map_err(|_| LoadError::parse(line, format!("expected a number, found `{token}`")))
+        })
+        .collect()
+}
+
+/// Converts a 1-based OBJ index, negative when relative to the end, to a
+/// 0-based one. Empty indices, as in `1//3`, give `None`.
+fn parse_index(
+    token: Option<&str>,
+    count: usize,
+    line: Option<usize>,
+) -> Result<Option<usize>, LoadError> {
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = token
+        .parse()
+        .map_err(|_| LoadError::parse(line, format!("expected an index, found `{token}`")))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(LoadError::parse(
+            line,
+            format!("index {index} out of range"),
+        ));
+    }
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUADS: &str = "
+# Two quads sharing an edge
+mtllib quads.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 2 0 0
+v 2 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g left
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g right
+usemtl blue
+f -5/1/-1 5/2/1 6/3/1 -4/4/1
+";
+
+    #[test]
+    fn triangulates_and_deduplicates() {
+        let model = parse_obj(QUADS).unwrap();
+        let mesh = &model.mesh;
+
+        // The quads share two positions but with different UVs, so no
+        // vertex is reused between them.
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_eq!(&mesh.indices[..6], &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.positions[4], Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.uvs.as_ref().unwrap()[2], Vector2::new(1.0, 0.0));
+        assert!(mesh
+            .normals
+            .as_ref()
+            .unwrap()
+            .iter()
+            .all(|normal| *normal == Vector3::new(0.0, 0.0, 1.0)));
+        assert!(mesh.colors.is_none());
+
+        assert_eq!(
+            model.groups,
+            vec![
+                ObjGroup {
+                    name: "left".into(),
+                    material: Some("red".into()),
+                    indices: 0..6,
+                },
+                ObjGroup {
+                    name: "right".into(),
+                    material: Some("blue".into()),
+                    indices: 6..12,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn computes_missing_normals() {
+        let model = parse_obj("v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\nf 1 2 3\n").unwrap();
+        let mesh = &model.mesh;
+        assert_eq!(
+            mesh.normals.as_ref().unwrap()[0],
+            Vector3::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            mesh.colors.as_ref().unwrap()[1],
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+        assert!(mesh.uvs.is_none());
+        assert_eq!(model.groups[0].indices, 0..3);
+    }
+
+    #[test]
+    fn written_files_parse_back() {
+        let mut mesh = crate::util::geometry::torus(1.0, 0.25, 12, 6);
+        mesh.set_color(Vector3::new(0.25, 0.5, 1.0));
+
+        let mut bytes = Vec::new();
+        write_obj(&mesh, &mut bytes).unwrap();
+        let loaded = parse_obj(std::str::from_utf8(&bytes).unwrap())
+            .unwrap()
+            .mesh;
+
+        assert_eq!(loaded.triangle_count(), mesh.triangle_count());
+        assert_eq!(loaded.vertex_count(), mesh.vertex_count());
+        for (&original, &index) in mesh.indices.iter().zip(&loaded.indices) {
+            let (original, index) = (original as usize, index as usize);
+            assert_eq!(loaded.positions[index], mesh.positions[original]);
+            assert_eq!(
+                loaded.normals.as_ref().unwrap()[index],
+                mesh.normals.as_ref().unwrap()[original]
+            );
+            let uv = loaded.uvs.as_ref().unwrap()[index];
+            assert!((uv - mesh.uvs.as_ref().unwrap()[original]).x.abs() < 1e-6);
+            assert!((uv - mesh.uvs.as_ref().unwrap()[original]).y.abs() < 1e-6);
+            assert_eq!(
+                loaded.colors.as_ref().unwrap()[index],
+                Vector3::new(0.25, 0.5, 1.0)
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_trailing_comments() {
+        let model = parse_obj(
+            "# a triangle\nv 0 0 0 # origin\nv 1 0 0\nv 0 1 0\nf 1 2 3 # the only face\n",
+        )
+        .unwrap();
+        assert_eq!(model.mesh.positions[0], Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(model.mesh.indices, vec![0, 1, 2]);
+
+        let materials = parse_mtl("newmtl red # bright\nKd 1 0 0 # red\n").unwrap();
+        assert_eq!(materials[0].name, "red");
+        assert_eq!(materials[0].diffuse, Vector3::new(1.0, 0.0, 0.0));
+
+        let libraries = mtl_libraries(
+            "mtllib a.mtl # shared materials
+mtllib b.mtl c.mtl
+",
+        );
+        assert_eq!(libraries, vec!["a.mtl", "b.mtl", "c.mtl"]);
+    }
+
+    #[test]
+    fn reports_bad_indices() {
+        let error = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap_err();
+        assert!(matches!(error, LoadError::Parse { line: Some(3), .. }));
+    }
+
+    #[test]
+    fn parses_materials() {
+        let materials = parse_mtl(
+            "newmtl red\nKd 1 0 0\nNs 32\nd 0.5\nmap_Kd red.png\nmap_Bump -bm 2 red_normal.png\n\
+             newmtl blue\nKd 0 0 1\nTr 0.25\n",
+        )
+        .unwrap();
+
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].diffuse, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(materials[0].shininess, 32.0);
+        assert_eq!(materials[0].opacity, 0.5);
+        assert_eq!(materials[0].diffuse_texture, Some(PathBuf::from("red.png")));
+        assert_eq!(
+            materials[0].normal_texture,
+            Some(PathBuf::from("red_normal.png"))
+        );
+        assert_eq!(materials[1].opacity, 0.75);
+    }
+
+    #[test]
+    fn loads_materials_next_to_the_obj() {
+        let dir = std::env::temp_dir().join(format!("wgpu-bootstrap-obj-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("quads.obj"), QUADS).unwrap();
+        std::fs::write(
+            dir.join("quads.mtl"),
+            "newmtl red\nKd 1 0 0\nmap_Kd red.png\nnewmtl blue\nKd 0 0 1\n",
+        )
+        .unwrap();
+
+        let model = load_obj(dir.join("quads.obj")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let red = model.material(&model.groups[0]).unwrap();
+        assert_eq!(red.name, "red");
+        assert_eq!(red.diffuse_texture, Some(dir.join("red.png")));
+        let blue = model.material(&model.groups[1]).unwrap();
+        assert_eq!(blue.diffuse, Vector3::new(0.0, 0.0, 1.0));
+    }
+}
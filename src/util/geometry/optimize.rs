@@ -0,0 +1,468 @@
+use cgmath::{prelude::*, Vector3};
+use std::collections::VecDeque;
+
+use super::{normalize_or_zero, Mesh};
+
+/// Size of the vertex cache [`optimize_vertex_cache`] optimizes for. Caches
+/// of current GPUs behave like FIFOs of 16 to 32 entries.
+const CACHE_SIZE: usize = 32;
+
+/// How well an index buffer uses a FIFO post-transform vertex cache.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VertexCacheStats {
+    /// Average cache miss ratio: vertices transformed per triangle, from 0.5
+    /// for long strips of a regular grid to 3 with no reuse at all.
+    pub acmr: f32,
+    /// Average transform to vertex ratio: vertices transformed per vertex
+    /// used, 1 at best.
+    pub atvr: f32,
+}
+
+/// Simulates a FIFO vertex cache of `cache_size` entries over a triangle
+/// list.
+pub fn analyze_vertex_cache(indices: &[u32], cache_size: usize) -> VertexCacheStats {
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    let mut used = Vec::new();
+    let mut misses = 0;
+    for &index in indices {
+        if used.len() <= index as usize {
+            used.resize(index as usize + 1, false);
+        }
+        used[index as usize] = true;
+        if !cache.contains(&index) {
+            misses += 1;
+            cache.push_back(index);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+
+    let triangles = indices.len() / 3;
+    let vertices = used.into_iter().filter(|&used| used).count();
+    VertexCacheStats {
+        acmr: misses as f32 / triangles.max(1) as f32,
+        atvr: misses as f32 / vertices.max(1) as f32,
+    }
+}
+
+/// Reorders the triangles so that vertices are reused while still in the
+/// post-transform cache, with Tom Forsyth's linear-speed algorithm. The
+/// vertices are left as they are.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+        if remaining == 0 {
+            return -1.0;
+        }
+        // The last triangle's vertices score a little lower, so that the
+        // next triangle does not just go back over the same edge.
+        let cache_score = match cache_position {
+            Some(position) if position < 3 => 0.75,
+            Some(position) => (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+            None => 0.0,
+        };
+        // Vertices with few triangles left are worth finishing.
+        cache_score + 2.0 / (remaining as f32).sqrt()
+    }
+
+    let triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+    let mut vertex_triangles = vec![Vec::new(); vertex_count];
+    for (triangle, corners) in triangles.iter().enumerate() {
+        for &corner in corners {
+            vertex_triangles[corner as usize].push(triangle as u32);
+        }
+    }
+
+    let mut remaining: Vec<usize> = vertex_triangles.iter().map(Vec::len).collect();
+    let mut cache_positions = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = remaining
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+    let mut triangle_scores: Vec<f32> = triangles
+        .iter()
+        .map(|corners| corners.iter().map(|&v| vertex_scores[v as usize]).sum())
+        .collect();
+    let mut emitted = vec![false; triangles.len()];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut result = Vec::with_capacity(triangles.len() * 3);
+    let mut cursor = 0;
+
+    for _ in 0..triangles.len() {
+        // The best triangle using a cached vertex, or when there is none,
+        // the next one not emitted yet.
+        let best = cache
+            .iter()
+            .flat_map(|&vertex| &vertex_triangles[vertex as usize])
+            .copied()
+            .filter(|&triangle| !emitted[triangle as usize])
+            .max_by(|&a, &b| triangle_scores[a as usize].total_cmp(&triangle_scores[b as usize]))
+            .unwrap_or_else(|| {
+                while emitted[cursor] {
+                    cursor += 1;
+                }
+                cursor as u32
+            });
+
+        let corners = triangles[best as usize];
+        emitted[best as usize] = true;
+        result.extend_from_slice(&corners);
+        for corner in corners {
+            remaining[corner as usize] -= 1;
+        }
+
+        let mut new_cache = corners.to_vec();
+        new_cache.extend(
+            cache
+                .iter()
+                .copied()
+                .filter(|vertex| !corners.contains(vertex)),
+        );
+        for &evicted in new_cache.iter().skip(CACHE_SIZE) {
+            cache_positions[evicted as usize] = None;
+        }
+        for (position, &vertex) in new_cache.iter().take(CACHE_SIZE).enumerate() {
+            cache_positions[vertex as usize] = Some(position);
+        }
+        for &vertex in &new_cache {
+            let vertex = vertex as usize;
+            vertex_scores[vertex] = vertex_score(cache_positions[vertex], remaining[vertex]);
+        }
+        for &vertex in &new_cache {
+            for &triangle in &vertex_triangles[vertex as usize] {
+                if !emitted[triangle as usize] {
+                    triangle_scores[triangle as usize] = triangles[triangle as usize]
+                        .iter()
+                        .map(|&v| vertex_scores[v as usize])
+                        .sum();
+                }
+            }
+        }
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+    }
+    result
+}
+
+/// Cache size [`optimize_overdraw`] keeps the cache use for.
+const OVERDRAW_CACHE_SIZE: usize = 16;
+
+/// How much [`optimize_overdraw`] lets the ACMR grow.
+const OVERDRAW_THRESHOLD: f32 = 1.05;
+
+/// Vertices of each triangle missing a FIFO cache of `cache_size` entries,
+/// starting empty.
+fn triangle_misses(indices: &[u32], cache_size: usize) -> Vec<u32> {
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    indices
+        .chunks_exact(3)
+        .map(|corners| {
+            let mut misses = 0;
+            for &corner in corners {
+                if !cache.contains(&corner) {
+                    misses += 1;
+                    cache.push_back(corner);
+                    if cache.len() > cache_size {
+                        cache.pop_front();
+                    }
+                }
+            }
+            misses
+        })
+        .collect()
+}
+
+/// First triangle of each cluster, followed by the triangle count.
+///
+/// Clusters start where all three vertices of a triangle miss the cache,
+/// which sorting them cannot make worse. With `split_on_misses`, these are
+/// split further before any other triangle with a miss, once the cluster
+/// so far would not miss more than [`OVERDRAW_THRESHOLD`] times as often as
+/// the one it is taken from when drawn after any other cluster, that is
+/// with an empty cache.
+fn cluster_starts(indices: &[u32], misses: &[u32], split_on_misses: bool) -> Vec<usize> {
+    let mut hard: Vec<usize> = (0..misses.len())
+        .filter(|&triangle| triangle == 0 || misses[triangle] == 3)
+        .collect();
+    hard.push(misses.len());
+    if !split_on_misses {
+        return hard;
+    }
+
+    let mut starts = Vec::new();
+    for range in hard.windows(2) {
+        let (start, end) = (range[0], range[1]);
+        let total: u32 = misses[start..end].iter().sum();
+        let limit = OVERDRAW_THRESHOLD * total as f32 / (end - start) as f32;
+
+        starts.push(start);
+        let (mut cluster_start, mut cluster_misses) = (start, 0);
+        let mut cache = VecDeque::with_capacity(OVERDRAW_CACHE_SIZE + 1);
+        for triangle in start..end {
+            for &corner in &indices[triangle * 3..triangle * 3 + 3] {
+                if !cache.contains(&corner) {
+                    cluster_misses += 1;
+                    cache.push_back(corner);
+                    if cache.len() > OVERDRAW_CACHE_SIZE {
+                        cache.pop_front();
+                    }
+                }
+            }
+            let next = triangle + 1;
+            let acmr = cluster_misses as f32 / (next - cluster_start) as f32;
+            if next < end && misses[next] > 0 && acmr <= limit {
+                starts.push(next);
+                (cluster_start, cluster_misses) = (next, 0);
+                cache.clear();
+            }
+        }
+    }
+    starts.push(misses.len());
+    starts
+}
+
+/// Reorders the clusters of an index buffer already optimized for the
+/// vertex cache so that the triangles facing outwards, which likely hide
+/// the others, are drawn first.
+///
+/// Clusters are split at the cache misses, as finely as keeps the ACMR for
+/// a cache of 16 entries within 5% of the input's. When the sorted order
+/// still goes past that, only the clusters starting where the cache is
+/// fully reloaded are sorted, or failing that the order is kept. Indices
+/// left over after the last full triangle are dropped.
+pub fn optimize_overdraw(positions: &[Vector3<f32>], indices: &[u32]) -> Vec<u32> {
+    let indices = &indices[..indices.len() - indices.len() % 3];
+    let misses = triangle_misses(indices, OVERDRAW_CACHE_SIZE);
+    let budget =
+        OVERDRAW_THRESHOLD * analyze_vertex_cache(indices, OVERDRAW_CACHE_SIZE).acmr + 1e-6;
+
+    let position = |index: u32| positions[index as usize];
+    let area_weighted = |triangles: &[u32]| {
+        triangles.chunks_exact(3).fold(
+            (Vector3::zero(), 0.0, Vector3::zero()),
+            |(sum, area, normal), corners| {
+                let [a, b, c] = [0, 1, 2].map(|k| position(corners[k]));
+                let cross = (b - a).cross(c - a);
+                let weight = cross.magnitude();
+                (
+                    sum + (a + b + c) / 3.0 * weight,
+                    area + weight,
+                    normal + cross,
+                )
+            },
+        )
+    };
+
+    let (sum, area, _) = area_weighted(indices);
+    let center = if area > 0.0 {
+        sum / area
+    } else {
+        Vector3::zero()
+    };
+    for split_on_misses in [true, false] {
+        let starts = cluster_starts(indices, &misses, split_on_misses);
+        let mut clusters: Vec<(f32, &[u32])> = starts
+            .windows(2)
+            .map(|range| {
+                let triangles = &indices[range[0] * 3..range[1] * 3];
+                let (sum, area, normal) = area_weighted(triangles);
+                let centroid = if area > 0.0 { sum / area } else { center };
+                (normalize_or_zero(normal).dot(centroid - center), triangles)
+            })
+            .collect();
+        clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let sorted: Vec<u32> = clusters
+            .into_iter()
+            .flat_map(|(_, triangles)| triangles)
+            .copied()
+            .collect();
+
+        if analyze_vertex_cache(&sorted, OVERDRAW_CACHE_SIZE).acmr <= budget {
+            return sorted;
+        }
+    }
+    indices.to_vec()
+}
+
+/// Renumbers the vertices in the order the triangles first use them, so
+/// they are fetched from memory mostly in sequence. Returns the new vertex
+/// of each old one and the old vertex of each new one. Unused vertices go
+/// last.
+fn fetch_order(indices: &[u32], vertex_count: usize) -> (Vec<u32>, Vec<u32>) {
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut sources = Vec::with_capacity(vertex_count);
+    let used = indices.iter().copied();
+    for vertex in used.chain(0..vertex_count as u32) {
+        if remap[vertex as usize] == u32::MAX {
+            remap[vertex as usize] = sources.len() as u32;
+            sources.push(vertex);
+        }
+    }
+    (remap, sources)
+}
+
+/// Renumbers the vertices in the order the triangles first use them, so
+/// they are fetched from memory mostly in sequence. Unused vertices go last.
+pub fn optimize_vertex_fetch(
+    positions: &[Vector3<f32>],
+    indices: &[u32],
+) -> (Vec<Vector3<f32>>, Vec<u32>) {
+    let (remap, sources) = fetch_order(indices, positions.len());
+    (
+        sources
+            .iter()
+            .map(|&source| positions[source as usize])
+            .collect(),
+        indices.iter().map(|&index| remap[index as usize]).collect(),
+    )
+}
+
+impl Mesh {
+    /// Reorders the triangles for the vertex cache, see
+    /// [`optimize_vertex_cache`].
+    pub fn optimize_vertex_cache(&mut self) -> &mut Self {
+        self.indices = optimize_vertex_cache(&self.indices, self.positions.len());
+        self
+    }
+
+    /// Reorders the triangles to reduce overdraw, see [`optimize_overdraw`].
+    pub fn optimize_overdraw(&mut self) -> &mut Self {
+        self.indices = optimize_overdraw(&self.positions, &self.indices);
+        self
+    }
+
+    /// Reorders the vertices for memory fetches, see
+    /// [`optimize_vertex_fetch`]. Every attribute follows.
+    pub fn optimize_vertex_fetch(&mut self) -> &mut Self {
+        let (remap, sources) = fetch_order(&self.indices, self.positions.len());
+        let indices = self
+            .indices
+            .iter()
+            .map(|&index| remap[index as usize])
+            .collect();
+        *self = self.remapped(&sources, indices);
+        self
+    }
+
+    /// Optimizes the vertex cache, then overdraw, then vertex fetches.
+    pub fn optimize(&mut self) -> &mut Self {
+        self.optimize_vertex_cache()
+            .optimize_overdraw()
+            .optimize_vertex_fetch()
+    }
+
+    /// See [`analyze_vertex_cache`].
+    pub fn vertex_cache_stats(&self, cache_size: usize) -> VertexCacheStats {
+        analyze_vertex_cache(&self.indices, cache_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::{icosphere, subdivide_midpoint, uv_sphere};
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                // Rotated so the smallest index comes first, like the
+                // winding of the original.
+                let first = (0..3).min_by_key(|&k| triangle[k]).unwrap();
+                [0, 1, 2].map(|k| triangle[(first + k) % 3])
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn analyzes_cache_use() {
+        let stats = analyze_vertex_cache(&[0, 1, 2, 0, 2, 3], 16);
+        assert_eq!(stats.acmr, 2.0);
+        assert_eq!(stats.atvr, 1.0);
+
+        let stats = analyze_vertex_cache(&[0, 1, 2, 3, 4, 5, 0, 1, 2], 3);
+        assert_eq!(stats.acmr, 3.0);
+        assert_eq!(stats.atvr, 1.5);
+    }
+
+    #[test]
+    fn improves_cache_use() {
+        // The midpoint split of a large sphere, without the cache
+        // optimization of `icosphere`.
+        let (mut positions, mut indices) = icosphere(0);
+        for _ in 0..5 {
+            (positions, indices) = subdivide_midpoint(&positions, &indices);
+        }
+        let optimized = optimize_vertex_cache(&indices, positions.len());
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+
+        let before = analyze_vertex_cache(&indices, 16);
+        let after = analyze_vertex_cache(&optimized, 16);
+        assert!(after.acmr < 0.8, "{before:?} -> {after:?}");
+        assert!(after.acmr < before.acmr, "{before:?} -> {after:?}");
+        assert!(analyze_vertex_cache(&icosphere(5).1, 16).acmr < 0.8);
+    }
+
+    #[test]
+    fn optimizes_whole_meshes() {
+        let original = uv_sphere(1.0, 32, 16);
+        let mut mesh = original.clone();
+        mesh.optimize();
+        assert_eq!(mesh.triangle_count(), original.triangle_count());
+        assert!(mesh.vertex_cache_stats(16).acmr < original.vertex_cache_stats(16).acmr);
+
+        // Vertices are numbered by first use and keep their attributes.
+        let mut fetched = original.clone();
+        fetched.optimize_vertex_fetch();
+        let mut next = 0;
+        for (&index, &original_index) in fetched.indices.iter().zip(&original.indices) {
+            assert!(index <= next);
+            next = next.max(index + 1);
+            let (index, original_index) = (index as usize, original_index as usize);
+            assert_eq!(fetched.positions[index], original.positions[original_index]);
+            let (uvs, original_uvs) = (fetched.uvs.as_ref(), original.uvs.as_ref());
+            assert_eq!(uvs.unwrap()[index], original_uvs.unwrap()[original_index]);
+        }
+    }
+
+    #[test]
+    fn overdraw_keeps_triangles() {
+        let (positions, indices) = icosphere(3);
+        let sorted = optimize_overdraw(&positions, &indices);
+        assert_eq!(sorted_triangles(&sorted), sorted_triangles(&indices));
+    }
+
+    #[test]
+    fn overdraw_keeps_leading_degenerate_triangles() {
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        // The first triangle repeats a vertex, so it never misses 3 times.
+        let indices = [0, 0, 1, 1, 2, 3, 7];
+        let sorted = optimize_overdraw(&positions, &indices);
+        assert_eq!(sorted_triangles(&sorted), sorted_triangles(&indices[..6]));
+    }
+
+    #[test]
+    fn overdraw_splits_at_cache_misses() {
+        let (positions, indices) = icosphere(3);
+        let misses = triangle_misses(&indices, OVERDRAW_CACHE_SIZE);
+        let hard = cluster_starts(&indices, &misses, false);
+        let soft = cluster_starts(&indices, &misses, true);
+        assert!(soft.len() > 2 * hard.len(), "{} {}", soft.len(), hard.len());
+
+        let sorted = optimize_overdraw(&positions, &indices);
+        assert_ne!(sorted, indices);
+        let before = analyze_vertex_cache(&indices, OVERDRAW_CACHE_SIZE);
+        let after = analyze_vertex_cache(&sorted, OVERDRAW_CACHE_SIZE);
+        assert!(after.acmr <= OVERDRAW_THRESHOLD * before.acmr + 1e-6);
+    }
+}
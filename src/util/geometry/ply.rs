@@ -0,0 +1,498 @@
+use cgmath::{Vector2, Vector3};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::{LoadError, Mesh};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Reads a PLY file.
+pub fn load_ply(path: impl AsRef<Path>) -> Result<Mesh, LoadError> {
+    parse_ply(&std::fs::read(path)?)
+}
+
+/// Parses a PLY file already loaded in memory.
+///
+/// Vertices may have normals (`nx`, `ny`, `nz`), UVs (`u`/`v`, `s`/`t` or
+/// `texture_u`/`texture_v`) and colors (`red`, `green`, `blue`). Faces are
+/// split in triangle fans. Files without faces give point clouds: meshes
+/// without indices, whose normals are not computed.
+pub fn parse_ply(bytes: &[u8]) -> Result<Mesh, LoadError> {
+    let header_end = bytes
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| LoadError::parse(None, "PLY without `end_header`"))?;
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |newline| header_end + newline + 1);
+    let header = std::str::from_utf8(&bytes[..header_end])
+        .map_err(|_| LoadError::parse(None, "PLY header is not ASCII"))?;
+
+    let (format, elements) = parse_header(header)?;
+    let mut body = match format {
+        PlyFormat::Ascii => Body::Ascii(
+            std::str::from_utf8(&bytes[body_start..])
+                .map_err(|_| LoadError::parse(None, "PLY body is not ASCII"))?
+                .split_whitespace(),
+        ),
+        _ => Body::Binary {
+            bytes: &bytes[body_start..],
+            position: 0,
+            big_endian: format == PlyFormat::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = Mesh::default();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut faces = false;
+
+    for element in &elements {
+        let slot = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|property| names.contains(&property.name.as_str()))
+        };
+        let position = [slot(&["x"]), slot(&["y"]), slot(&["z"])];
+        let normal = [slot(&["nx"]), slot(&["ny"]), slot(&["nz"])];
+        let uv = [
+            slot(&["u", "s", "texture_u"]),
+            slot(&["v", "t", "texture_v"]),
+        ];
+        let color = [slot(&["red"]), slot(&["green"]), slot(&["blue"])];
+        let face = slot(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            let mut values = Vec::with_capacity(element.properties.len());
+            let mut list = Vec::new();
+            for (k, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    Kind::Scalar(scalar) => values.push(body.read(scalar)?),
+                    Kind::List(count, item) => {
+                        values.push(0.0);
+                        let count = body.read(count)? as usize;
+                        let items = (0..count)
+                            .map(|_| body.read(item))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if Some(k) == face {
+                            list = items;
+                        }
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let [Some(x), Some(y), Some(z)] = position else {
+                        return Err(LoadError::parse(None, "vertices need x, y and z"));
+                    };
+                    mesh.positions.push(Vector3::new(
+                        values[x] as f32,
+                        values[y] as f32,
+                        values[z] as f32,
+                    ));
+                    if let [Some(x), Some(y), Some(z)] = normal {
+                        normals.push(Vector3::new(
+                            values[x] as f32,
+                            values[y] as f32,
+                            values[z] as f32,
+                        ));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        // PLY UVs start at the bottom, WGPU ones at the top.
+                        uvs.push(Vector2::new(values[u] as f32, 1.0 - values[v] as f32));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        let channel = |k: usize| match element.properties[k].kind {
+                            Kind::Scalar(Scalar::U8) => values[k] as f32 / 255.0,
+                            _ => values[k] as f32,
+                        };
+                        colors.push(Vector3::new(channel(r), channel(g), channel(b)));
+                    }
+                }
+                "face" => {
+                    faces = true;
+                    for k in 1..list.len().saturating_sub(1) {
+                        mesh.indices.extend_from_slice(&[
+                            list[0] as u32,
+                            list[k] as u32,
+                            list[k + 1] as u32,
+                        ]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let count = mesh.positions.len();
+    if mesh.indices.iter().any(|&index| index as usize >= count) {
+        return Err(LoadError::parse(None, "face index out of range"));
+    }
+    if normals.len() == count && !normals.is_empty() {
+        mesh.normals = Some(normals);
+    } else if faces {
+        mesh.compute_normals();
+    }
+    if uvs.len() == count && !uvs.is_empty() {
+        mesh.uvs = Some(uvs);
+    }
+    if colors.len() == count && !colors.is_empty() {
+        mesh.colors = Some(colors);
+    }
+    Ok(mesh)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Kind {
+    Scalar(Scalar),
+    /// Count type and item type.
+    List(Scalar, Scalar),
+}
+
+struct Property {
+    name: String,
+    kind: Kind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn parse_header(header: &str) -> Result<(PlyFormat, Vec<Element>), LoadError> {
+    let mut lines = header.lines().enumerate();
+    if lines.next().map(|(_, line)| line.trim()) != Some("ply") {
+        return Err(LoadError::parse(Some(1), "not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (number, line) in lines {
+        let number = Some(number + 1);
+        let scalar = |name: &str| {
+            Scalar::parse(name)
+                .ok_or_else(|| LoadError::parse(number, format!("unknown type `{name}`")))
+        };
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(LoadError::parse(number, "unknown PLY format")),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| LoadError::parse(number, "invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| LoadError::parse(number, "property before `element`"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Kind::List(scalar(count)?, scalar(item)?),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| LoadError::parse(number, "property before `element`"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Kind::Scalar(scalar(kind)?),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| LoadError::parse(None, "PLY without `format`"))?;
+    Ok((format, elements))
+}
+
+enum Body<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        position: usize,
+        big_endian: bool,
+    },
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, LoadError> {
+        match self {
+            Body::Ascii(tokens) => tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or_else(|| LoadError::parse(None, "missing or invalid PLY value")),
+            Body::Binary {
+                bytes,
+                position,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                let mut value = [0u8; 8];
+                value[..size].copy_from_slice(
+                    bytes
+                        .get(*position..*position + size)
+                        .ok_or_else(|| LoadError::parse(None, "truncated PLY body"))?,
+                );
+                *position += size;
+                if *big_endian {
+                    value[..size].reverse();
+                }
+                let value = &value[..size];
+                Ok(match scalar {
+                    Scalar::I8 => value[0] as i8 as f64,
+                    Scalar::U8 => value[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Scalar::U16 => u16::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Scalar::I32 => i32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Scalar::U32 => u32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Scalar::F32 => f32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Scalar::F64 => f64::from_le_bytes(value.try_into().unwrap()),
+                })
+            }
+        }
+    }
+}
+
+pub fn save_ply(mesh: &Mesh, path: impl AsRef<Path>, format: PlyFormat) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ply(mesh, &mut writer, format)?;
+    writer.flush()
+}
+
+/// Writes the positions of `mesh` and its normals, UVs and colors when it
+/// has some. Colors are stored as bytes. Meshes without indices are written
+/// as point clouds, without a face element.
+pub fn write_ply(mesh: &Mesh, writer: &mut impl Write, format: PlyFormat) -> std::io::Result<()> {
+    writeln!(writer, "ply")?;
+    let name = match format {
+        PlyFormat::Ascii => "ascii",
+        PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        PlyFormat::BinaryBigEndian => "binary_big_endian",
+    };
+    writeln!(writer, "format {name} 1.0")?;
+    writeln!(writer, "comment wgpu-bootstrap")?;
+    writeln!(writer, "element vertex {}", mesh.positions.len())?;
+    for name in ["x", "y", "z"] {
+        writeln!(writer, "property float {name}")?;
+    }
+    if mesh.normals.is_some() {
+        for name in ["nx", "ny", "nz"] {
+            writeln!(writer, "property float {name}")?;
+        }
+    }
+    if mesh.uvs.is_some() {
+        for name in ["u", "v"] {
+            writeln!(writer, "property float {name}")?;
+        }
+    }
+    if mesh.colors.is_some() {
+        for name in ["red", "green", "blue"] {
+            writeln!(writer, "property uchar {name}")?;
+        }
+    }
+    if !mesh.indices.is_empty() {
+        writeln!(writer, "element face {}", mesh.triangle_count())?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    let float = |writer: &mut dyn Write, value: f32| match format {
+        PlyFormat::Ascii => write!(writer, "{value} "),
+        PlyFormat::BinaryLittleEndian => writer.write_all(&value.to_le_bytes()),
+        PlyFormat::BinaryBigEndian => writer.write_all(&value.to_be_bytes()),
+    };
+    let byte = |writer: &mut dyn Write, value: u8| match format {
+        PlyFormat::Ascii => write!(writer, "{value} "),
+        _ => writer.write_all(&[value]),
+    };
+    let uint = |writer: &mut dyn Write, value: u32| match format {
+        PlyFormat::Ascii => write!(writer, "{value} "),
+        PlyFormat::BinaryLittleEndian => writer.write_all(&value.to_le_bytes()),
+        PlyFormat::BinaryBigEndian => writer.write_all(&value.to_be_bytes()),
+    };
+    let end_line = |writer: &mut dyn Write| match format {
+        PlyFormat::Ascii => writeln!(writer),
+        _ => Ok(()),
+    };
+
+    for (i, position) in mesh.positions.iter().enumerate() {
+        for value in [position.x, position.y, position.z] {
+            float(writer, value)?;
+        }
+        if let Some(normals) = &mesh.normals {
+            for value in [normals[i].x, normals[i].y, normals[i].z] {
+                float(writer, value)?;
+            }
+        }
+        if let Some(uvs) = &mesh.uvs {
+            float(writer, uvs[i].x)?;
+            float(writer, 1.0 - uvs[i].y)?;
+        }
+        if let Some(colors) = &mesh.colors {
+            for value in [colors[i].x, colors[i].y, colors[i].z] {
+                byte(writer, (value.clamp(0.0, 1.0) * 255.0).round() as u8)?;
+            }
+        }
+        end_line(writer)?;
+    }
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        byte(writer, 3)?;
+        for &index in triangle {
+            uint(writer, index)?;
+        }
+        end_line(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::uv_sphere;
+    use cgmath::prelude::*;
+
+    fn round_trip(format: PlyFormat) {
+        let mut mesh = uv_sphere(1.0, 8, 4);
+        mesh.tangents = None;
+        mesh.colors = Some(
+            mesh.positions
+                .iter()
+                .map(|position| position.map(|c| (c * 0.5 + 0.5) * 255.0).map(f32::round) / 255.0)
+                .collect(),
+        );
+
+        let mut bytes = Vec::new();
+        write_ply(&mesh, &mut bytes, format).unwrap();
+        let loaded = parse_ply(&bytes).unwrap();
+
+        assert_eq!(loaded.positions, mesh.positions);
+        assert_eq!(loaded.normals, mesh.normals);
+        assert_eq!(loaded.indices, mesh.indices);
+        let uvs = loaded.uvs.unwrap();
+        for (loaded, uv) in uvs.iter().zip(mesh.uvs.as_ref().unwrap()) {
+            assert!((loaded - uv).magnitude() < 1e-6);
+        }
+        for (loaded, color) in loaded.colors.unwrap().iter().zip(mesh.colors.unwrap()) {
+            assert!((loaded - color).magnitude() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        round_trip(PlyFormat::Ascii);
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        round_trip(PlyFormat::BinaryLittleEndian);
+        round_trip(PlyFormat::BinaryBigEndian);
+    }
+
+    #[test]
+    fn point_cloud_round_trip() {
+        let mesh = Mesh::new(
+            vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(-1.0, 0.5, 0.0)],
+            Vec::new(),
+        );
+        let mut bytes = Vec::new();
+        write_ply(&mesh, &mut bytes, PlyFormat::BinaryLittleEndian).unwrap();
+        let loaded = parse_ply(&bytes).unwrap();
+
+        assert_eq!(loaded.positions, mesh.positions);
+        assert!(loaded.indices.is_empty());
+        assert!(loaded.normals.is_none());
+    }
+
+    #[test]
+    fn reads_quads_and_skips_unknown_elements() {
+        let source = "ply
+format ascii 1.0
+comment a quad and an edge
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_index
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 255 0 0
+1 0 0 255 0 0
+1 1 0 255 0 0
+0 1 0 255 0 0
+4 0 1 2 3
+0 2
+";
+        let mesh = parse_ply(source.as_bytes()).unwrap();
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.colors.unwrap()[3], Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.normals.unwrap()[0], Vector3::new(0.0, 0.0, 1.0));
+    }
+}
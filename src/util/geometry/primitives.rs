@@ -0,0 +1,471 @@
+use cgmath::{prelude::*, Vector2, Vector3};
+use std::f32::consts::PI;
+
+use super::Mesh;
+
+// All generators wind triangles counter-clockwise seen from outside. UVs
+// start at the top left of each surface, like texture coordinates, and
+// tangents point towards increasing u.
+
+/// A box centered on the origin. Each face has its own four vertices, so
+/// normals stay sharp and every face gets the whole texture.
+pub fn cuboid(width: f32, height: f32, depth: f32) -> Mesh {
+    let size = Vector3::new(width, height, depth);
+    let x = Vector3::unit_x();
+    let y = Vector3::unit_y();
+    let z = Vector3::unit_z();
+
+    // (normal, right, up) with right x up = normal.
+    let faces = [
+        (z, x, y),
+        (-z, -x, y),
+        (x, -z, y),
+        (-x, z, y),
+        (y, x, -z),
+        (-y, x, z),
+    ];
+
+    let mut builder = Builder::default();
+    for (normal, right, up) in faces {
+        let center = normal.mul_element_wise(size) / 2.0;
+        let right_extent = right.mul_element_wise(size);
+        let up_extent = up.mul_element_wise(size);
+        builder.grid(1, 1, false, false, |i, j| {
+            let (u, v) = (i as f32, j as f32);
+            Vertex {
+                position: center + right_extent * (u - 0.5) + up_extent * (0.5 - v),
+                normal,
+                uv: Vector2::new(u, v),
+                tangent: right,
+            }
+        });
+    }
+    builder.build()
+}
+
+/// A plane in XZ facing +Y, centered on the origin and split in
+/// `columns` x `rows` quads.
+pub fn plane(width: f32, depth: f32, columns: u32, rows: u32) -> Mesh {
+    let columns = columns.max(1);
+    let rows = rows.max(1);
+
+    let mut builder = Builder::default();
+    builder.grid(columns, rows, false, false, |i, j| {
+        let u = i as f32 / columns as f32;
+        let v = j as f32 / rows as f32;
+        Vertex {
+            position: Vector3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth),
+            normal: Vector3::unit_y(),
+            uv: Vector2::new(u, v),
+            tangent: Vector3::unit_x(),
+        }
+    });
+    builder.build()
+}
+
+/// A sphere made of `segments` meridians and `rings` parallels. The UVs are
+/// an equirectangular mapping, centered on +Z with the seam at -Z.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+
+    let mut builder = Builder::default();
+    builder.grid(segments, rings, true, true, |i, j| {
+        let u = i as f32 / segments as f32;
+        let v = j as f32 / rings as f32;
+        let phi = (u - 0.5) * 2.0 * PI;
+        let normal = spherical(phi, v * PI);
+        Vertex {
+            position: normal * radius,
+            normal,
+            uv: Vector2::new(u, v),
+            tangent: around(phi),
+        }
+    });
+    builder.build()
+}
+
+/// A cylinder along Y, centered on the origin, with caps.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+    let top = height / 2.0;
+    let mut builder = Builder::default();
+    builder.band(segments, (0.0, top), (radius, top));
+    builder.band(segments, (radius, top), (radius, -top));
+    builder.band(segments, (radius, -top), (0.0, -top));
+    builder.build()
+}
+
+/// A cone along Y with its apex at `height / 2` and its base closed.
+pub fn cone(radius: f32, height: f32, segments: u32) -> Mesh {
+    let top = height / 2.0;
+    let mut builder = Builder::default();
+    builder.band(segments, (0.0, top), (radius, -top));
+    builder.band(segments, (radius, -top), (0.0, -top));
+    builder.build()
+}
+
+/// A disc in XZ facing +Y.
+pub fn disc(radius: f32, segments: u32) -> Mesh {
+    let mut builder = Builder::default();
+    builder.band(segments, (0.0, 0.0), (radius, 0.0));
+    builder.build()
+}
+
+/// A torus around Y. `major_radius` goes from the center to the middle of
+/// the tube, `segments` are counted around Y and `sides` around the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Mesh {
+    let segments = segments.max(3);
+    let sides = sides.max(3);
+
+    let mut builder = Builder::default();
+    builder.grid(segments, sides, false, false, |i, j| {
+        let u = i as f32 / segments as f32;
+        let v = j as f32 / sides as f32;
+        let (phi, theta) = (u * 2.0 * PI, v * 2.0 * PI);
+        let radial = Vector3::new(phi.sin(), 0.0, phi.cos());
+        let normal = radial * theta.cos() - Vector3::unit_y() * theta.sin();
+        Vertex {
+            position: radial * major_radius + normal * minor_radius,
+            normal,
+            uv: Vector2::new(u, v),
+            tangent: around(phi),
+        }
+    });
+    builder.build()
+}
+
+/// A cylinder along Y ended by two hemispheres. `height` is the length of
+/// the cylindrical part, so the capsule measures `height + 2 * radius`.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Mesh {
+    let segments = segments.max(3);
+    let rings = rings.max(1);
+
+    // Rows go down the top hemisphere then the bottom one, the quads
+    // between the two equators forming the cylinder. V follows the length
+    // of the profile so the texture is not stretched on the cylinder.
+    let length = PI * radius + height;
+    let row = |j: u32| -> (f32, f32, f32) {
+        if j <= rings {
+            let theta = j as f32 / rings as f32 * PI / 2.0;
+            (theta, height / 2.0, theta * radius)
+        } else {
+            let theta = PI / 2.0 + (j - rings - 1) as f32 / rings as f32 * PI / 2.0;
+            (theta, -height / 2.0, theta * radius + height)
+        }
+    };
+
+    let mut builder = Builder::default();
+    builder.grid(segments, 2 * rings + 1, true, true, |i, j| {
+        let u = i as f32 / segments as f32;
+        let (theta, offset, arc) = row(j);
+        let phi = (u - 0.5) * 2.0 * PI;
+        let normal = spherical(phi, theta);
+        Vertex {
+            position: normal * radius + Vector3::unit_y() * offset,
+            normal,
+            uv: Vector2::new(u, arc / length),
+            tangent: around(phi),
+        }
+    });
+    builder.build()
+}
+
+/// An arrow along +Y starting at the origin: a shaft closed at its base
+/// followed by a cone.
+pub fn arrow(
+    shaft_radius: f32,
+    shaft_length: f32,
+    head_radius: f32,
+    head_length: f32,
+    segments: u32,
+) -> Mesh {
+    let tip = shaft_length + head_length;
+    let mut builder = Builder::default();
+    builder.band(segments, (0.0, tip), (head_radius, shaft_length));
+    builder.band(
+        segments,
+        (head_radius, shaft_length),
+        (shaft_radius, shaft_length),
+    );
+    builder.band(segments, (shaft_radius, shaft_length), (shaft_radius, 0.0));
+    builder.band(segments, (shaft_radius, 0.0), (0.0, 0.0));
+    builder.build()
+}
+
+/// Point of the unit sphere at longitude `phi` and angle `theta` from +Y.
+fn spherical(phi: f32, theta: f32) -> Vector3<f32> {
+    Vector3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        theta.sin() * phi.cos(),
+    )
+}
+
+/// Direction of increasing longitude `phi` around Y.
+fn around(phi: f32) -> Vector3<f32> {
+    Vector3::new(phi.cos(), 0.0, -phi.sin())
+}
+
+struct Vertex {
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+    uv: Vector2<f32>,
+    tangent: Vector3<f32>,
+}
+
+#[derive(Default)]
+struct Builder {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Vector2<f32>>,
+    tangents: Vec<Vector3<f32>>,
+    indices: Vec<u32>,
+}
+
+impl Builder {
+    /// Adds `(columns + 1) x (rows + 1)` vertices given by `vertex(i, j)`,
+    /// with `i` along u and `j` along v, and two triangles per quad. When the
+    /// top or bottom row collapses to a point, the triangles it would make
+    /// degenerate are left out.
+    fn grid(
+        &mut self,
+        columns: u32,
+        rows: u32,
+        collapse_top: bool,
+        collapse_bottom: bool,
+        vertex: impl Fn(u32, u32) -> Vertex,
+    ) {
+        let base = self.positions.len() as u32;
+        for j in 0..=rows {
+            for i in 0..=columns {
+                let vertex = vertex(i, j);
+                self.positions.push(vertex.position);
+                self.normals.push(vertex.normal);
+                self.uvs.push(vertex.uv);
+                self.tangents.push(vertex.tangent);
+            }
+        }
+
+        let index = |i: u32, j: u32| base + j * (columns + 1) + i;
+        for j in 0..rows {
+            for i in 0..columns {
+                let a = index(i, j);
+                let b = index(i, j + 1);
+                let c = index(i + 1, j + 1);
+                let d = index(i + 1, j);
+                if !(collapse_bottom && j + 1 == rows) {
+                    self.indices.extend_from_slice(&[a, b, c]);
+                }
+                if !(collapse_top && j == 0) {
+                    self.indices.extend_from_slice(&[a, c, d]);
+                }
+            }
+        }
+    }
+
+    /// Adds the surface swept by the segment from `top` to `bottom`, given
+    /// as `(radius, y)`, around Y. Flat bands get planar UVs, the others
+    /// cylindrical ones. A segment of length zero, which has no normal,
+    /// adds nothing.
+    fn band(&mut self, segments: u32, top: (f32, f32), bottom: (f32, f32)) {
+        let segments = segments.max(3);
+        let (dr, dy) = (bottom.0 - top.0, bottom.1 - top.1);
+        if dr == 0.0 && dy == 0.0 {
+            return;
+        }
+        let profile_normal = Vector2::new(-dy, dr).normalize();
+        let flat = dy == 0.0;
+        let extent = top.0.max(bottom.0);
+
+        self.grid(segments, 1, top.0 == 0.0, bottom.0 == 0.0, |i, j| {
+            let u = i as f32 / segments as f32;
+            let phi = u * 2.0 * PI;
+            let radial = Vector3::new(phi.sin(), 0.0, phi.cos());
+            let (radius, y) = if j == 0 { top } else { bottom };
+            let position = radial * radius + Vector3::unit_y() * y;
+            let normal = radial * profile_normal.x + Vector3::unit_y() * profile_normal.y;
+
+            if flat {
+                Vertex {
+                    position,
+                    normal,
+                    uv: Vector2::new(
+                        0.5 + position.x / (2.0 * extent),
+                        0.5 + profile_normal.y * position.z / (2.0 * extent),
+                    ),
+                    tangent: Vector3::unit_x(),
+                }
+            } else {
+                Vertex {
+                    position,
+                    normal,
+                    uv: Vector2::new(u, j as f32),
+                    tangent: around(phi),
+                }
+            }
+        });
+    }
+
+    fn build(self) -> Mesh {
+        Mesh {
+            positions: self.positions,
+            normals: Some(self.normals),
+            uvs: Some(self.uvs),
+            tangents: Some(self.tangents),
+            colors: None,
+            indices: self.indices,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Checks attribute lengths, unit normals and tangents, tangents
+    /// orthogonal to normals and triangles facing the way of their normals.
+    fn assert_well_formed(mesh: &Mesh) {
+        let count = mesh.positions.len();
+        let normals = mesh.normals.as_ref().unwrap();
+        let uvs = mesh.uvs.as_ref().unwrap();
+        let tangents = mesh.tangents.as_ref().unwrap();
+        assert_eq!(normals.len(), count);
+        assert_eq!(uvs.len(), count);
+        assert_eq!(tangents.len(), count);
+        assert_eq!(mesh.indices.len() % 3, 0);
+        assert!(mesh.indices.iter().all(|&index| (index as usize) < count));
+
+        for ((normal, tangent), uv) in normals.iter().zip(tangents).zip(uvs) {
+            assert!((normal.magnitude() - 1.0).abs() < 1e-4);
+            assert!((tangent.magnitude() - 1.0).abs() < 1e-4);
+            assert!(normal.dot(*tangent).abs() < 1e-4);
+            assert!((-1e-4..=1.0 + 1e-4).contains(&uv.x));
+            assert!((-1e-4..=1.0 + 1e-4).contains(&uv.y));
+        }
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| triangle[k] as usize);
+            let p = &mesh.positions;
+            let face = (p[b] - p[a]).cross(p[c] - p[a]);
+            assert!(face.magnitude() > 1e-8, "degenerate triangle {triangle:?}");
+            let normal = normals[a] + normals[b] + normals[c];
+            assert!(face.dot(normal) > 0.0, "triangle {triangle:?} is inverted");
+        }
+    }
+
+    /// Counts the edges used by a single triangle once vertices at the same
+    /// position are merged, and checks that no edge is used twice in the
+    /// same direction.
+    fn boundary_edges(mesh: &Mesh) -> usize {
+        let key = |index: u32| {
+            let p = mesh.positions[index as usize];
+            [p.x, p.y, p.z].map(|c| (c * 1e4).round() as i32)
+        };
+
+        let mut edges = HashMap::new();
+        for triangle in mesh.indices.chunks_exact(3) {
+            for k in 0..3 {
+                let edge = (key(triangle[k]), key(triangle[(k + 1) % 3]));
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+
+        assert!(edges.values().all(|&uses| uses == 1));
+        edges
+            .keys()
+            .filter(|(a, b)| !edges.contains_key(&(*b, *a)))
+            .count()
+    }
+
+    fn assert_closed(mesh: &Mesh) {
+        assert_well_formed(mesh);
+        assert_eq!(boundary_edges(mesh), 0);
+    }
+
+    #[test]
+    fn cuboid_is_closed() {
+        let mesh = cuboid(1.0, 2.0, 3.0);
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn plane_has_a_boundary() {
+        let mesh = plane(2.0, 1.0, 4, 3);
+        assert_eq!(mesh.vertex_count(), 5 * 4);
+        assert_eq!(mesh.triangle_count(), 2 * 4 * 3);
+        assert_well_formed(&mesh);
+        assert_eq!(boundary_edges(&mesh), 2 * (4 + 3));
+    }
+
+    #[test]
+    fn uv_sphere_is_closed() {
+        let mesh = uv_sphere(2.0, 16, 8);
+        assert_eq!(mesh.vertex_count(), 17 * 9);
+        assert_eq!(mesh.triangle_count(), 16 * (2 * 8 - 2));
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn cylinder_is_closed() {
+        let mesh = cylinder(1.0, 2.0, 12);
+        assert_eq!(mesh.vertex_count(), 3 * 2 * 13);
+        assert_eq!(mesh.triangle_count(), 4 * 12);
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn cone_is_closed() {
+        let mesh = cone(1.0, 2.0, 12);
+        assert_eq!(mesh.vertex_count(), 2 * 2 * 13);
+        assert_eq!(mesh.triangle_count(), 2 * 12);
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn disc_has_a_boundary() {
+        let mesh = disc(1.0, 12);
+        assert_eq!(mesh.vertex_count(), 2 * 13);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_well_formed(&mesh);
+        assert_eq!(boundary_edges(&mesh), 12);
+    }
+
+    #[test]
+    fn torus_is_closed() {
+        let mesh = torus(1.0, 0.25, 24, 12);
+        assert_eq!(mesh.vertex_count(), 25 * 13);
+        assert_eq!(mesh.triangle_count(), 2 * 24 * 12);
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn capsule_is_closed() {
+        let mesh = capsule(0.5, 1.0, 16, 4);
+        assert_eq!(mesh.vertex_count(), 17 * (2 * 4 + 2));
+        assert_eq!(mesh.triangle_count(), 16 * 4 * 4);
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn arrow_is_closed() {
+        let mesh = arrow(0.1, 1.0, 0.2, 0.3, 12);
+        assert_eq!(mesh.vertex_count(), 4 * 2 * 13);
+        assert_eq!(mesh.triangle_count(), 6 * 12);
+        assert_closed(&mesh);
+    }
+
+    #[test]
+    fn zero_length_profiles_are_skipped() {
+        // Two caps back to back.
+        let mesh = cylinder(1.0, 0.0, 12);
+        assert_eq!(mesh.triangle_count(), 2 * 12);
+        assert_well_formed(&mesh);
+
+        let mesh = arrow(0.1, 1.0, 0.1, 0.3, 12);
+        assert_eq!(mesh.triangle_count(), 4 * 12);
+        assert_closed(&mesh);
+    }
+}
@@ -0,0 +1,501 @@
+use cgmath::{prelude::*, Vector3};
+use std::{cmp::Ordering, collections::BinaryHeap, ops::AddAssign};
+
+use super::{cleanup::position_groups, HalfEdgeMesh, Mesh};
+
+/// How much more moving away from a boundary or seam costs than moving
+/// away from a face.
+const BOUNDARY_WEIGHT: f64 = 10.0;
+
+/// Removes vertices by collapsing them onto a neighbor, cheapest first by
+/// quadric error, until `target_triangles` triangles remain or the next
+/// collapse would move the surface by more than about `max_error`. Returns
+/// the new indices, into the same vertices.
+///
+/// Vertices sharing a position are seams, where the UVs or the normals
+/// are discontinuous. They only move along the seam, together, and the
+/// boundaries only move along themselves, so neither opens up. Non-manifold
+/// vertices and where seams meet boundaries stay in place.
+pub fn simplify(
+    positions: &[Vector3<f32>],
+    indices: &[u32],
+    target_triangles: usize,
+    max_error: f32,
+) -> Vec<u32> {
+    let mut simplifier = Simplifier::new(positions, indices);
+    simplifier.run(target_triangles, (max_error as f64).powi(2));
+    simplifier.indices()
+}
+
+impl Mesh {
+    /// A simplified copy of the mesh, see [`simplify`]. The remaining
+    /// vertices keep all their attributes.
+    pub fn simplify(&self, target_triangles: usize, max_error: f32) -> Mesh {
+        let mut mesh = self.clone();
+        mesh.indices = simplify(&self.positions, &self.indices, target_triangles, max_error);
+        mesh.remove_unreferenced_vertices();
+        mesh
+    }
+
+    /// Index buffers of decreasing detail, all for the vertices of the mesh,
+    /// starting with its own indices. Each level has about `ratio` times the
+    /// triangles of the previous one. Fewer than `levels` are returned when
+    /// the mesh cannot be simplified further.
+    pub fn lod_chain(&self, levels: usize, ratio: f32) -> Vec<Vec<u32>> {
+        let mut chain = vec![self.indices.clone()];
+        while chain.len() < levels {
+            let previous = chain.last().unwrap();
+            let target = (previous.len() / 3) as f32 * ratio;
+            let indices = simplify(&self.positions, previous, target as usize, f32::INFINITY);
+            if indices.len() == previous.len() {
+                break;
+            }
+            chain.push(indices);
+        }
+        chain
+    }
+}
+
+/// Sum of squared distances to planes, as a symmetric 4x4 matrix.
+#[derive(Debug, Copy, Clone, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(normal: Vector3<f64>, point: Vector3<f64>, weight: f64) -> Self {
+        let [a, b, c] = [normal.x, normal.y, normal.z];
+        let d = -normal.dot(point);
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|x| x * weight),
+        )
+    }
+
+    fn error(&self, point: Vector3<f64>) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let Vector3 { x, y, z } = point;
+        let error = aa * x * x
+            + bb * y * y
+            + cc * z * z
+            + dd
+            + 2.0 * (ab * x * y + ac * x * z + bc * y * z + ad * x + bd * y + cd * z);
+        error.max(0.0)
+    }
+}
+
+impl AddAssign for Quadric {
+    fn add_assign(&mut self, other: Self) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Interior,
+    /// On a boundary of the surface.
+    Border,
+    /// One of the two copies of a vertex on a seam.
+    Seam,
+    Locked,
+}
+
+/// Moving the first vertex of each pair onto the second one. Seams move
+/// both of their copies.
+struct Collapse {
+    cost: f64,
+    moves: Vec<(u32, u32)>,
+    version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // Reversed, so the heap gives the cheapest collapse first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier<'a> {
+    positions: &'a [Vector3<f32>],
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    live: usize,
+    vertex_triangles: Vec<Vec<u32>>,
+    kinds: Vec<Kind>,
+    /// First vertex with the same position, which holds the quadric.
+    groups: Vec<u32>,
+    /// Other copy of each seam vertex.
+    seam_copies: Vec<Option<u32>>,
+    quadrics: Vec<Quadric>,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(positions: &'a [Vector3<f32>], indices: &[u32]) -> Self {
+        let vertex_count = positions.len();
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        let mut vertex_triangles = vec![Vec::new(); vertex_count];
+        for (triangle, corners) in triangles.iter().enumerate() {
+            for &corner in corners {
+                vertex_triangles[corner as usize].push(triangle as u32);
+            }
+        }
+
+        let groups = position_groups(positions);
+        let mut members: Vec<Vec<u32>> = vec![Vec::new(); vertex_count];
+        for (vertex, &group) in groups.iter().enumerate() {
+            members[group as usize].push(vertex as u32);
+        }
+
+        let half_edges = HalfEdgeMesh::new(vertex_count, indices);
+        let welded_indices: Vec<u32> = indices
+            .iter()
+            .map(|&index| groups[index as usize])
+            .collect();
+        let welded = HalfEdgeMesh::new(vertex_count, &welded_indices);
+        let mut seam_copies = vec![None; vertex_count];
+        let kinds = (0..vertex_count as u32)
+            .map(|vertex| {
+                let group = groups[vertex as usize];
+                let copies = &members[group as usize];
+                let manifold = half_edges.vertex_fans(vertex).len() == 1
+                    && welded.vertex_fans(group).len() == 1;
+                match copies.as_slice() {
+                    _ if !manifold => Kind::Locked,
+                    [_] if half_edges.is_boundary_vertex(vertex) => Kind::Border,
+                    [_] => Kind::Interior,
+                    &[a, b] if !welded.is_boundary_vertex(group) => {
+                        seam_copies[vertex as usize] = Some(if a == vertex { b } else { a });
+                        Kind::Seam
+                    }
+                    _ => Kind::Locked,
+                }
+            })
+            .collect();
+
+        let position = |vertex: u32| positions[vertex as usize].cast::<f64>().unwrap();
+        let mut quadrics = vec![Quadric::default(); vertex_count];
+        for (triangle, &[a, b, c]) in triangles.iter().enumerate() {
+            let normal = (position(b) - position(a)).cross(position(c) - position(a));
+            if normal.magnitude2() == 0.0 {
+                continue;
+            }
+            let normal = normal.normalize();
+            let plane = Quadric::plane(normal, position(a), 1.0);
+            for corner in [a, b, c] {
+                quadrics[groups[corner as usize] as usize] += plane;
+            }
+
+            // Planes along the boundaries and seams, perpendicular to the
+            // face, keep them from moving sideways.
+            for k in 0..3 {
+                let half_edge = triangle as u32 * 3 + k;
+                if !half_edges.is_boundary(half_edge) {
+                    continue;
+                }
+                let (from, to) = (half_edges.origin(half_edge), half_edges.target(half_edge));
+                let side = (position(to) - position(from)).cross(normal);
+                if side.magnitude2() == 0.0 {
+                    continue;
+                }
+                let plane = Quadric::plane(side.normalize(), position(from), BOUNDARY_WEIGHT);
+                for corner in [from, to] {
+                    quadrics[groups[corner as usize] as usize] += plane;
+                }
+            }
+        }
+
+        let mut simplifier = Self {
+            positions,
+            live: triangles.len(),
+            alive: vec![true; triangles.len()],
+            triangles,
+            vertex_triangles,
+            kinds,
+            groups,
+            seam_copies,
+            quadrics,
+            removed: vec![false; vertex_count],
+            versions: vec![0; vertex_count],
+            heap: BinaryHeap::new(),
+        };
+        for vertex in 0..vertex_count as u32 {
+            simplifier.update(vertex);
+        }
+        simplifier
+    }
+
+    fn run(&mut self, target_triangles: usize, max_error: f64) {
+        while self.live > target_triangles {
+            let Some(collapse) = self.heap.pop() else {
+                break;
+            };
+            let (from, _) = collapse.moves[0];
+            if self.removed[from as usize] || collapse.version != self.versions[from as usize] {
+                continue;
+            }
+            if collapse.cost > max_error {
+                break;
+            }
+            if !collapse
+                .moves
+                .iter()
+                .all(|&(from, to)| self.can_move(from, to))
+            {
+                self.update(from);
+                continue;
+            }
+
+            let mut touched = Vec::new();
+            for &(from, to) in &collapse.moves {
+                self.apply(from, to);
+                touched.push(to);
+                touched.extend(self.neighbors(to));
+            }
+            let (from, to) = collapse.moves[0];
+            let quadric = self.quadrics[self.groups[from as usize] as usize];
+            self.quadrics[self.groups[to as usize] as usize] += quadric;
+            for vertex in touched {
+                self.update(vertex);
+            }
+        }
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        self.triangles
+            .iter()
+            .zip(&self.alive)
+            .filter(|(_, &alive)| alive)
+            .flat_map(|(triangle, _)| *triangle)
+            .collect()
+    }
+
+    /// Queues the cheapest valid collapse of `vertex`, replacing the one
+    /// already queued.
+    fn update(&mut self, vertex: u32) {
+        self.versions[vertex as usize] += 1;
+        if self.removed[vertex as usize] {
+            return;
+        }
+        let position = |vertex: u32| self.positions[vertex as usize].cast::<f64>().unwrap();
+        let quadric = |vertex: u32| self.quadrics[self.groups[vertex as usize] as usize];
+
+        let mut best: Option<Collapse> = None;
+        for moves in self.candidates(vertex) {
+            if !moves.iter().all(|&(from, to)| self.can_move(from, to)) {
+                continue;
+            }
+            let (from, to) = moves[0];
+            let mut sum = quadric(from);
+            sum += quadric(to);
+            let cost = sum.error(position(to));
+            if best.as_ref().is_none_or(|best| cost < best.cost) {
+                best = Some(Collapse {
+                    cost,
+                    moves,
+                    version: self.versions[vertex as usize],
+                });
+            }
+        }
+        self.heap.extend(best);
+    }
+
+    /// The collapses of `vertex` allowed by its kind.
+    fn candidates(&self, vertex: u32) -> Vec<Vec<(u32, u32)>> {
+        let neighbors = self.neighbors(vertex);
+        match self.kinds[vertex as usize] {
+            Kind::Interior => neighbors.into_iter().map(|to| vec![(vertex, to)]).collect(),
+            Kind::Border => neighbors
+                .into_iter()
+                .filter(|&to| self.shared_triangles(vertex, to) == 1)
+                .map(|to| vec![(vertex, to)])
+                .collect(),
+            Kind::Seam => {
+                let copy = self.seam_copies[vertex as usize].unwrap();
+                let copy_neighbors = self.neighbors(copy);
+                neighbors
+                    .into_iter()
+                    .filter(|&to| self.shared_triangles(vertex, to) == 1)
+                    .filter_map(|to| {
+                        let copy_to = copy_neighbors.iter().copied().find(|&other| {
+                            self.groups[other as usize] == self.groups[to as usize]
+                                && self.shared_triangles(copy, other) == 1
+                        })?;
+                        Some(vec![(vertex, to), (copy, copy_to)])
+                    })
+                    .collect()
+            }
+            Kind::Locked => Vec::new(),
+        }
+    }
+
+    /// Whether moving `from` onto `to` keeps the surface manifold and flips
+    /// no triangle.
+    fn can_move(&self, from: u32, to: u32) -> bool {
+        if self.removed[from as usize] || self.removed[to as usize] {
+            return false;
+        }
+        // The link condition: the vertices connected to both are only the
+        // opposite corners of the triangles on the edge.
+        let shared = self.shared_triangles(from, to);
+        let to_neighbors = self.neighbors(to);
+        let common = self
+            .neighbors(from)
+            .into_iter()
+            .filter(|neighbor| to_neighbors.contains(neighbor))
+            .count();
+        if shared == 0 || common != shared {
+            return false;
+        }
+
+        let position = |vertex: u32| self.positions[vertex as usize];
+        self.live_triangles(from).all(|triangle| {
+            let corners = self.triangles[triangle as usize];
+            if corners.contains(&to) {
+                return true;
+            }
+            let normal = |corners: [u32; 3]| {
+                let [a, b, c] = corners.map(position);
+                (b - a).cross(c - a)
+            };
+            let moved = corners.map(|corner| if corner == from { to } else { corner });
+            let (before, after) = (normal(corners), normal(moved));
+            after.magnitude2() > 0.0 && before.dot(after) > 0.0
+        })
+    }
+
+    fn apply(&mut self, from: u32, to: u32) {
+        for triangle in std::mem::take(&mut self.vertex_triangles[from as usize]) {
+            if !self.alive[triangle as usize] {
+                continue;
+            }
+            let corners = &mut self.triangles[triangle as usize];
+            if corners.contains(&to) {
+                self.alive[triangle as usize] = false;
+                self.live -= 1;
+            } else {
+                for corner in corners.iter_mut().filter(|corner| **corner == from) {
+                    *corner = to;
+                }
+                self.vertex_triangles[to as usize].push(triangle);
+            }
+        }
+        self.removed[from as usize] = true;
+    }
+
+    fn live_triangles(&self, vertex: u32) -> impl Iterator<Item = u32> + '_ {
+        self.vertex_triangles[vertex as usize]
+            .iter()
+            .copied()
+            .filter(|&triangle| self.alive[triangle as usize])
+    }
+
+    fn neighbors(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self
+            .live_triangles(vertex)
+            .flat_map(|triangle| self.triangles[triangle as usize])
+            .filter(|&other| other != vertex)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn shared_triangles(&self, a: u32, b: u32) -> usize {
+        self.live_triangles(a)
+            .filter(|&triangle| self.triangles[triangle as usize].contains(&b))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::{icosphere, plane, uv_sphere};
+
+    /// The report of the mesh once its seams are welded.
+    fn welded(mesh: &Mesh) -> crate::util::geometry::ValidationReport {
+        let mut mesh = Mesh::new(mesh.positions.clone(), mesh.indices.clone());
+        mesh.weld(1e-5);
+        mesh.validate()
+    }
+
+    #[test]
+    fn flat_grid_collapses_to_corners() {
+        let grid = plane(2.0, 2.0, 8, 8);
+        let simplified = grid.simplify(0, 1e-4);
+        assert_eq!(simplified.triangle_count(), 2);
+        assert_eq!(simplified.vertex_count(), 4);
+        for position in &simplified.positions {
+            assert_eq!(position.x.abs(), 1.0);
+            assert_eq!(position.z.abs(), 1.0);
+        }
+        assert!(simplified.uvs.is_some());
+    }
+
+    #[test]
+    fn keeps_seams_closed() {
+        let sphere = uv_sphere(1.0, 32, 16);
+        let simplified = sphere.simplify(sphere.triangle_count() / 4, f32::INFINITY);
+        assert!(simplified.triangle_count() <= sphere.triangle_count() / 4);
+        assert!(simplified.triangle_count() > 0);
+
+        let report = welded(&simplified);
+        assert!(report.is_closed_manifold(), "{report}");
+        assert_eq!(report.euler_characteristic(), 2);
+    }
+
+    #[test]
+    fn stops_at_max_error() {
+        let (positions, indices) = icosphere(2);
+        assert_eq!(simplify(&positions, &indices, 0, 1e-4), indices);
+
+        let coarse = simplify(&positions, &indices, 0, 0.2);
+        assert!(coarse.len() < indices.len());
+    }
+
+    #[test]
+    fn builds_lod_chains() {
+        let (positions, indices) = icosphere(3);
+        let sphere = Mesh::new(positions, indices);
+        let chain = sphere.lod_chain(4, 0.5);
+        assert_eq!(chain.len(), 4);
+        assert_eq!(chain[0], sphere.indices);
+        for level in 1..chain.len() {
+            let (previous, current) = (chain[level - 1].len() / 3, chain[level].len() / 3);
+            assert!(current <= previous / 2);
+            let lod = Mesh::new(sphere.positions.clone(), chain[level].clone());
+            assert!(lod.validate().is_closed_manifold());
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use cgmath::{prelude::*, Vector3};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::{compute_triangle_normal, normalize_or_zero, LoadError, Mesh};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+/// Reads an ASCII or binary STL file.
+pub fn load_stl(path: impl AsRef<Path>) -> Result<Mesh, LoadError> {
+    parse_stl(&std::fs::read(path)?)
+}
+
+/// Parses an ASCII or binary STL file already loaded in memory.
+///
+/// STL stores independent triangles, so each one gets its own three
+/// vertices, with the normal of the facet.
+pub fn parse_stl(bytes: &[u8]) -> Result<Mesh, LoadError> {
+    // Binary files may also start with "solid", their size tells them apart.
+    let binary = bytes.len() >= 84 && {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        bytes.len() == 84 + count * 50
+    };
+    if binary || !bytes.starts_with(b"solid") {
+        parse_binary(bytes)
+    } else {
+        let source =
+            std::str::from_utf8(bytes).map_err(|_| LoadError::parse(None, "STL is not UTF-8"))?;
+        parse_ascii(source)
+    }
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Mesh, LoadError> {
+    let count = bytes
+        .get(80..84)
+        .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+        .ok_or_else(|| LoadError::parse(None, "truncated STL header"))?;
+    let facets = bytes
+        .get(84..84 + count * 50)
+        .ok_or_else(|| LoadError::parse(None, "truncated STL facets"))?;
+
+    let mut builder = Facets::default();
+    for facet in facets.chunks_exact(50) {
+        let vector = |k: usize| {
+            let float = |i: usize| {
+                let start = k * 12 + i * 4;
+                f32::from_le_bytes(facet[start..start + 4].try_into().unwrap())
+            };
+            Vector3::new(float(0), float(1), float(2))
+        };
+        builder.push(vector(0), &[vector(1), vector(2), vector(3)]);
+    }
+    Ok(builder.build())
+}
+
+fn parse_ascii(source: &str) -> Result<Mesh, LoadError> {
+    let mut builder = Facets::default();
+    let mut normal = Vector3::zero();
+    let mut corners = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let number = Some(number + 1);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let vector = |tokens: &[&str]| -> Result<Vector3<f32>, LoadError> {
+            let values = tokens
+                .iter()
+                .map(|token| token.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .filter(|values| values.len() == 3)
+                .ok_or_else(|| LoadError::parse(number, "expected 3 numbers"))?;
+            Ok(Vector3::new(values[0], values[1], values[2]))
+        };
+
+        match tokens.as_slice() {
+            ["facet", "normal", rest @ ..] => {
+                normal = vector(rest)?;
+                corners.clear();
+            }
+            ["vertex", rest @ ..] => corners.push(vector(rest)?),
+            ["endfacet"] => {
+                if corners.len() < 3 {
+                    return Err(LoadError::parse(number, "a facet needs 3 vertices"));
+                }
+                builder.push(normal, &corners);
+            }
+            _ => {}
+        }
+    }
+    Ok(builder.build())
+}
+
+#[derive(Default)]
+struct Facets {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+}
+
+impl Facets {
+    /// Adds a facet as a triangle fan. Missing normals are computed.
+    fn push(&mut self, normal: Vector3<f32>, corners: &[Vector3<f32>]) {
+        for k in 1..corners.len() - 1 {
+            let triangle = [corners[0], corners[k], corners[k + 1]];
+            let normal = match normalize_or_zero(normal) {
+                normal if normal.is_zero() => {
+                    compute_triangle_normal(&triangle[0], &triangle[1], &triangle[2])
+                }
+                normal => normal,
+            };
+            self.positions.extend_from_slice(&triangle);
+            self.normals.extend_from_slice(&[normal; 3]);
+        }
+    }
+
+    fn build(self) -> Mesh {
+        let indices = (0..self.positions.len() as u32).collect();
+        let mut mesh = Mesh::new(self.positions, indices);
+        mesh.normals = Some(self.normals);
+        mesh
+    }
+}
+
+pub fn save_stl(mesh: &Mesh, path: impl AsRef<Path>, format: StlFormat) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_stl(mesh, &mut writer, format)?;
+    writer.flush()
+}
+
+/// Writes the triangles of `mesh` with normals computed from their positions.
+/// Other attributes are left out.
+pub fn write_stl(mesh: &Mesh, writer: &mut impl Write, format: StlFormat) -> std::io::Result<()> {
+    let triangles = mesh.indices.chunks_exact(3).map(|triangle| {
+        let [a, b, c] = [0, 1, 2].map(|k| mesh.positions[triangle[k] as usize]);
+        (normalize_or_zero((b - a).cross(c - a)), [a, b, c])
+    });
+
+    match format {
+        StlFormat::Ascii => {
+            writeln!(writer, "solid mesh")?;
+            for (normal, corners) in triangles {
+                writeln!(
+                    writer,
+                    "facet normal {} {} {}",
+                    normal.x, normal.y, normal.z
+                )?;
+                writeln!(writer, "  outer loop")?;
+                for corner in corners {
+                    writeln!(writer, "    vertex {} {} {}", corner.x, corner.y, corner.z)?;
+                }
+                writeln!(writer, "  endloop")?;
+                writeln!(writer, "endfacet")?;
+            }
+            writeln!(writer, "endsolid mesh")
+        }
+        StlFormat::Binary => {
+            let mut header = [0u8; 80];
+            header[..14].copy_from_slice(b"wgpu-bootstrap");
+            writer.write_all(&header)?;
+            writer.write_all(&(mesh.triangle_count() as u32).to_le_bytes())?;
+            for (normal, corners) in triangles {
+                for vector in std::iter::once(normal).chain(corners) {
+                    for value in [vector.x, vector.y, vector.z] {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                }
+                writer.write_all(&0u16.to_le_bytes())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::cuboid;
+
+    fn round_trip(format: StlFormat) {
+        let mesh = cuboid(1.0, 2.0, 3.0);
+        let mut bytes = Vec::new();
+        write_stl(&mesh, &mut bytes, format).unwrap();
+        let loaded = parse_stl(&bytes).unwrap();
+
+        assert_eq!(loaded.triangle_count(), mesh.triangle_count());
+        assert_eq!(loaded.vertex_count(), 3 * mesh.triangle_count());
+        for (k, &index) in mesh.indices.iter().enumerate() {
+            assert_eq!(loaded.positions[k], mesh.positions[index as usize]);
+            let normal = loaded.normals.as_ref().unwrap()[k];
+            let expected = mesh.normals.as_ref().unwrap()[index as usize];
+            assert!((normal - expected).magnitude() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        round_trip(StlFormat::Ascii);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(StlFormat::Binary);
+    }
+
+    #[test]
+    fn reads_binary_starting_with_solid() {
+        let mut bytes = Vec::new();
+        write_stl(&cuboid(1.0, 1.0, 1.0), &mut bytes, StlFormat::Binary).unwrap();
+        bytes[..5].copy_from_slice(b"solid");
+        assert_eq!(parse_stl(&bytes).unwrap().triangle_count(), 12);
+    }
+}
@@ -0,0 +1,495 @@
+use cgmath::{prelude::*, Vector3};
+use std::{collections::HashMap, f32::consts::PI};
+
+use super::{edge_key, normalize_or_zero, HalfEdgeMesh, Mesh};
+
+/// Splits each triangle in four at the middle of its edges. The existing
+/// vertices keep their indices and the new ones are shared by the triangles
+/// on both sides of an edge.
+pub fn subdivide_midpoint(
+    positions: &[Vector3<f32>],
+    indices: &[u32],
+) -> (Vec<Vector3<f32>>, Vec<u32>) {
+    let half_edges = HalfEdgeMesh::new(positions.len(), indices);
+    let (edges, indices) = split_triangles(&half_edges);
+    let midpoints = edges.into_iter().map(|half_edge| {
+        let (a, b) = (half_edges.origin(half_edge), half_edges.target(half_edge));
+        (positions[a as usize] + positions[b as usize]) / 2.0
+    });
+    (
+        positions.iter().copied().chain(midpoints).collect(),
+        indices,
+    )
+}
+
+impl Mesh {
+    /// Splits each triangle in four at the middle of its edges, see
+    /// [`subdivide_midpoint`]. The attributes of the new vertices are
+    /// interpolated.
+    pub fn subdivide_midpoint(&self) -> Mesh {
+        let half_edges = self.half_edges();
+        let (edges, indices) = split_triangles(&half_edges);
+        let sources =
+            self.keep_and_add(edges.iter().map(|&half_edge| {
+                vec![half_edges.origin(half_edge), half_edges.target(half_edge)]
+            }));
+        self.averaged(&sources, indices)
+    }
+
+    /// One step of Loop subdivision, which splits each triangle in four and
+    /// smooths the surface. Repeated steps converge to a smooth surface.
+    ///
+    /// Edges whose faces meet at more than `crease_angle` (in radians) stay
+    /// sharp, like boundaries and the edges of vertices split along seams,
+    /// and vertices with more than two sharp edges stay in place. An angle
+    /// of `PI` only keeps the boundaries. Other attributes are interpolated
+    /// and normals and tangents, if any, are recomputed.
+    pub fn subdivide_loop(&self, crease_angle: f32) -> Mesh {
+        let half_edges = self.half_edges();
+        let (edges, indices) = split_triangles(&half_edges);
+        let sharp = self.sharp_edges(&half_edges, crease_angle);
+        let position = |vertex: u32| self.positions[vertex as usize];
+
+        let mut positions: Vec<Vector3<f32>> = (0..self.positions.len() as u32)
+            .map(|vertex| {
+                let mut creases: Vec<u32> = Vec::new();
+                for &half_edge in half_edges.outgoing(vertex) {
+                    if sharp[half_edge as usize] {
+                        creases.push(half_edges.target(half_edge));
+                    }
+                    let incoming = half_edges.prev(half_edge);
+                    if half_edges.is_boundary(incoming) {
+                        creases.push(half_edges.origin(incoming));
+                    }
+                }
+                creases.retain(|&other| other != vertex);
+                creases.sort_unstable();
+                creases.dedup();
+
+                let neighbors = half_edges.vertex_neighbors(vertex);
+                match creases.as_slice() {
+                    [a, b] => position(vertex) * 0.75 + (position(*a) + position(*b)) * 0.125,
+                    [_, _, _, ..] => position(vertex),
+                    _ if neighbors.len() < 3 => position(vertex),
+                    _ => {
+                        let n = neighbors.len() as f32;
+                        let cos = (2.0 * PI / n).cos();
+                        let beta = (0.625 - (0.375 + 0.25 * cos).powi(2)) / n;
+                        let sum = neighbors
+                            .iter()
+                            .fold(Vector3::zero(), |sum, &other| sum + position(other));
+                        position(vertex) * (1.0 - n * beta) + sum * beta
+                    }
+                }
+            })
+            .collect();
+        positions.extend(edges.iter().map(|&half_edge| {
+            let (a, b) = (half_edges.origin(half_edge), half_edges.target(half_edge));
+            match half_edges.twin(half_edge) {
+                Some(twin) if !sharp[half_edge as usize] => {
+                    let c = half_edges.origin(half_edges.prev(half_edge));
+                    let d = half_edges.origin(half_edges.prev(twin));
+                    (position(a) + position(b)) * 0.375 + (position(c) + position(d)) * 0.125
+                }
+                _ => (position(a) + position(b)) * 0.5,
+            }
+        }));
+
+        let sources =
+            self.keep_and_add(edges.iter().map(|&half_edge| {
+                vec![half_edges.origin(half_edge), half_edges.target(half_edge)]
+            }));
+        let mut mesh = self.averaged(&sources, indices);
+        mesh.positions = positions;
+        mesh.recompute_shading(self);
+        mesh
+    }
+
+    /// One step of Catmull-Clark subdivision of the triangles of the mesh,
+    /// which splits each of them in three quads and smooths the surface.
+    ///
+    /// Quads split in triangles are subdivided as two triangles; pass them
+    /// to [`subdivide_catmull_clark_faces`](Self::subdivide_catmull_clark_faces)
+    /// instead.
+    pub fn subdivide_catmull_clark(&self, crease_angle: f32) -> Mesh {
+        let faces: Vec<Vec<u32>> = self.indices.chunks_exact(3).map(<[u32]>::to_vec).collect();
+        self.subdivide_catmull_clark_faces(&faces, crease_angle)
+    }
+
+    /// One step of Catmull-Clark subdivision of the polygons `faces`, given
+    /// as vertices of the mesh in order, which splits each polygon in quads,
+    /// one per corner, and smooths the surface. The indices of the mesh are
+    /// ignored.
+    ///
+    /// The quads are written as triangles `(a, b, c)` and `(a, c, d)`, which
+    /// [`quad_faces`](Self::quad_faces) turns back into faces. Creases and
+    /// attributes are handled like in [`subdivide_loop`](Self::subdivide_loop).
+    pub fn subdivide_catmull_clark_faces(&self, faces: &[Vec<u32>], crease_angle: f32) -> Mesh {
+        let min_cos = crease_angle.cos() - 1e-4;
+        let position = |vertex: u32| self.positions[vertex as usize];
+        let vertex_count = self.positions.len();
+
+        let mut edge_numbers: HashMap<u64, usize> = HashMap::new();
+        let mut edges: Vec<([u32; 2], Vec<usize>)> = Vec::new();
+        let mut vertex_edges = vec![Vec::new(); vertex_count];
+        let mut vertex_faces = vec![Vec::new(); vertex_count];
+        for (face, corners) in faces.iter().enumerate() {
+            for (k, &a) in corners.iter().enumerate() {
+                let b = corners[(k + 1) % corners.len()];
+                let edge = *edge_numbers.entry(edge_key(a, b)).or_insert_with(|| {
+                    edges.push(([a, b], Vec::new()));
+                    vertex_edges[a as usize].push(edges.len() - 1);
+                    vertex_edges[b as usize].push(edges.len() - 1);
+                    edges.len() - 1
+                });
+                edges[edge].1.push(face);
+                vertex_faces[a as usize].push(face);
+            }
+        }
+
+        let face_points: Vec<Vector3<f32>> = faces
+            .iter()
+            .map(|corners| {
+                let sum = corners
+                    .iter()
+                    .fold(Vector3::zero(), |sum, &corner| sum + position(corner));
+                sum / corners.len() as f32
+            })
+            .collect();
+        // Newell's method, which also works for non-planar polygons.
+        let face_normals: Vec<Vector3<f32>> = faces
+            .iter()
+            .map(|corners| {
+                let normal = (0..corners.len()).fold(Vector3::zero(), |sum, k| {
+                    let next = corners[(k + 1) % corners.len()];
+                    sum + position(corners[k]).cross(position(next))
+                });
+                normalize_or_zero(normal)
+            })
+            .collect();
+        let sharp: Vec<bool> = edges
+            .iter()
+            .map(|(_, faces)| match faces.as_slice() {
+                &[f, g] => face_normals[f].dot(face_normals[g]) < min_cos,
+                _ => true,
+            })
+            .collect();
+        let midpoint = |edge: usize| {
+            let [a, b] = edges[edge].0;
+            (position(a) + position(b)) * 0.5
+        };
+
+        let mut positions: Vec<Vector3<f32>> = (0..vertex_count)
+            .map(|vertex| {
+                let around = &vertex_edges[vertex];
+                let creases: Vec<usize> =
+                    around.iter().copied().filter(|&edge| sharp[edge]).collect();
+                let v = position(vertex as u32);
+                match creases.as_slice() {
+                    &[a, b] => v * 0.5 + (midpoint(a) + midpoint(b)) * 0.25,
+                    [_, _, _, ..] => v,
+                    _ if around.len() < 3 => v,
+                    _ => {
+                        let n = around.len() as f32;
+                        let faces = &vertex_faces[vertex];
+                        let q = faces
+                            .iter()
+                            .fold(Vector3::zero(), |sum, &face| sum + face_points[face])
+                            / faces.len() as f32;
+                        let r = around
+                            .iter()
+                            .fold(Vector3::zero(), |sum, &edge| sum + midpoint(edge))
+                            / n;
+                        (q + r * 2.0 + v * (n - 3.0)) / n
+                    }
+                }
+            })
+            .collect();
+        positions.extend((0..edges.len()).map(|edge| match edges[edge].1.as_slice() {
+            &[f, g] if !sharp[edge] => {
+                (midpoint(edge) * 2.0 + face_points[f] + face_points[g]) / 4.0
+            }
+            _ => midpoint(edge),
+        }));
+        positions.extend(face_points.iter().copied());
+
+        let edge_point = |a: u32, b: u32| (vertex_count + edge_numbers[&edge_key(a, b)]) as u32;
+        let face_point = |face: usize| (vertex_count + edges.len() + face) as u32;
+        let mut indices = Vec::new();
+        for (face, corners) in faces.iter().enumerate() {
+            let m = corners.len();
+            for k in 0..m {
+                let (prev, corner, next) =
+                    (corners[(k + m - 1) % m], corners[k], corners[(k + 1) % m]);
+                let (before, after) = (edge_point(prev, corner), edge_point(corner, next));
+                indices.extend_from_slice(&[corner, after, face_point(face)]);
+                indices.extend_from_slice(&[corner, face_point(face), before]);
+            }
+        }
+
+        let sources = self.keep_and_add(
+            edges
+                .iter()
+                .map(|(ends, _)| ends.to_vec())
+                .chain(faces.iter().cloned()),
+        );
+        let mut mesh = self.averaged(&sources, indices);
+        mesh.positions = positions;
+        mesh.recompute_shading(self);
+        mesh
+    }
+
+    /// The quads of a mesh made only of them, each written as consecutive
+    /// triangles `(a, b, c)` and `(a, c, d)` like the primitive generators
+    /// and [`subdivide_catmull_clark_faces`](Self::subdivide_catmull_clark_faces)
+    /// do, or `None` if any pair of triangles is not such a quad.
+    pub fn quad_faces(&self) -> Option<Vec<Vec<u32>>> {
+        self.indices
+            .chunks(6)
+            .map(|pair| match *pair {
+                [a, b, c, first, second, d]
+                    if [first, second] == [a, c] && ![a, b, c].contains(&d) =>
+                {
+                    Some(vec![a, b, c, d])
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// For each half-edge, whether it is on a boundary or its faces meet at
+    /// more than `crease_angle`.
+    fn sharp_edges(&self, half_edges: &HalfEdgeMesh, crease_angle: f32) -> Vec<bool> {
+        let min_cos = crease_angle.cos() - 1e-4;
+        let normal = |half_edge: u32| {
+            let [a, b, c] = half_edges
+                .face_vertices(half_edges.face(half_edge))
+                .map(|vertex| self.positions[vertex as usize]);
+            normalize_or_zero((b - a).cross(c - a))
+        };
+        (0..half_edges.half_edge_count() as u32)
+            .map(|half_edge| match half_edges.twin(half_edge) {
+                Some(twin) => normal(half_edge).dot(normal(twin)) < min_cos,
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Sources of the existing vertices, each its own, followed by `added`.
+    fn keep_and_add(&self, added: impl Iterator<Item = Vec<u32>>) -> Vec<Vec<u32>> {
+        (0..self.positions.len() as u32)
+            .map(|vertex| vec![vertex])
+            .chain(added)
+            .collect()
+    }
+
+    /// A mesh whose vertex `i` has the average of the attributes of the
+    /// vertices `sources[i]` of this one, with new indices.
+    fn averaged(&self, sources: &[Vec<u32>], indices: Vec<u32>) -> Mesh {
+        fn average<V: VectorSpace<Scalar = f32>>(values: &[V], sources: &[Vec<u32>]) -> Vec<V> {
+            sources
+                .iter()
+                .map(|sources| {
+                    let sum = sources
+                        .iter()
+                        .fold(V::zero(), |sum, &source| sum + values[source as usize]);
+                    sum / sources.len() as f32
+                })
+                .collect()
+        }
+        let unit =
+            |vectors: Vec<Vector3<f32>>| vectors.into_iter().map(normalize_or_zero).collect();
+
+        Mesh {
+            positions: average(&self.positions, sources),
+            normals: self
+                .normals
+                .as_deref()
+                .map(|normals| unit(average(normals, sources))),
+            uvs: self.uvs.as_deref().map(|uvs| average(uvs, sources)),
+            tangents: self
+                .tangents
+                .as_deref()
+                .map(|tangents| unit(average(tangents, sources))),
+            colors: self
+                .colors
+                .as_deref()
+                .map(|colors| average(colors, sources)),
+            indices,
+        }
+    }
+
+    /// Recomputes the normals and tangents the `original` mesh had, after
+    /// the positions moved.
+    fn recompute_shading(&mut self, original: &Mesh) {
+        if original.normals.is_some() {
+            self.compute_normals();
+        }
+        if original.tangents.is_some() && self.uvs.is_some() {
+            self.compute_tangents();
+        }
+    }
+}
+
+/// Numbers the middle of each edge after the existing vertices and splits
+/// each triangle in four. Returns a half-edge of each edge, in the order of
+/// the new vertices, and the new indices.
+fn split_triangles(half_edges: &HalfEdgeMesh) -> (Vec<u32>, Vec<u32>) {
+    let vertex_count = half_edges.vertex_count() as u32;
+    let mut numbers: HashMap<u64, u32> = HashMap::new();
+    let mut edges = Vec::new();
+    let mut indices = Vec::with_capacity(half_edges.half_edge_count() * 4);
+
+    for face in 0..half_edges.face_count() as u32 {
+        let [a, b, c] = [0, 1, 2].map(|k| {
+            let half_edge = face * 3 + k;
+            let (from, to) = (half_edges.origin(half_edge), half_edges.target(half_edge));
+            *numbers.entry(edge_key(from, to)).or_insert_with(|| {
+                edges.push(half_edge);
+                vertex_count + edges.len() as u32 - 1
+            })
+        });
+        let [v1, v2, v3] = half_edges.face_vertices(face);
+        indices.extend_from_slice(&[v1, a, c, v2, b, a, v3, c, b, a, b, c]);
+    }
+    (edges, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::{cuboid, icosphere, plane};
+
+    /// Unit cube with its corners welded, as 6 quads.
+    fn welded_cube() -> Mesh {
+        let cube = cuboid(1.0, 1.0, 1.0);
+        let mut cube = Mesh::new(cube.positions, cube.indices);
+        cube.weld(1e-5);
+        cube
+    }
+
+    fn on_cube(position: Vector3<f32>) -> bool {
+        let max = position.x.abs().max(position.y.abs()).max(position.z.abs());
+        (max - 0.5).abs() < 1e-5
+    }
+
+    #[test]
+    fn midpoint_matches_icosphere() {
+        let (positions, indices) = icosphere(0);
+        let (positions, indices) = subdivide_midpoint(&positions, &indices);
+        let sphere = Mesh::new(positions, indices);
+        assert_eq!(sphere.vertex_count(), 42);
+        assert_eq!(sphere.triangle_count(), 80);
+        assert!(sphere.validate().is_closed_manifold());
+        let (expected, _) = icosphere(1);
+        for position in &sphere.positions {
+            let position = position.normalize();
+            assert!(expected
+                .iter()
+                .any(|&other| (other - position).magnitude() < 1e-6));
+        }
+
+        let mesh = cuboid(1.0, 1.0, 1.0).subdivide_midpoint();
+        assert_eq!(mesh.triangle_count(), 48);
+        assert_eq!(mesh.vertex_count(), 24 + 6 * 5);
+        assert!(mesh.positions.iter().all(|&position| on_cube(position)));
+        assert_eq!(mesh.uvs.as_ref().unwrap().len(), mesh.vertex_count());
+    }
+
+    #[test]
+    fn loop_smooths_closed_meshes() {
+        let (positions, indices) = icosphere(0);
+        let mesh = Mesh::new(positions, indices).subdivide_loop(PI);
+        assert_eq!(mesh.vertex_count(), 12 + 30);
+        assert_eq!(mesh.triangle_count(), 80);
+        assert!(mesh.validate().is_closed_manifold());
+
+        // Every vertex of the icosahedron is alike, so they all end up at
+        // the same distance from the center, and edges likewise.
+        let radius = mesh.positions[0].magnitude();
+        for position in &mesh.positions[..12] {
+            assert!((position.magnitude() - radius).abs() < 1e-5);
+        }
+        assert!(radius < 1.0);
+    }
+
+    #[test]
+    fn loop_keeps_creases_and_boundaries() {
+        let cube = welded_cube().subdivide_loop(PI / 4.0);
+        assert!(cube.positions.iter().all(|&position| on_cube(position)));
+        assert_eq!(cube.positions[..8], welded_cube().positions[..]);
+
+        let smooth = welded_cube().subdivide_loop(PI);
+        assert!(!smooth.positions.iter().all(|&position| on_cube(position)));
+
+        // The boundary of a flat grid stays where it is.
+        let grid = plane(2.0, 2.0, 2, 2).subdivide_loop(PI);
+        for position in &grid.positions {
+            assert!(position.y.abs() < 1e-6);
+            assert!(position.x.abs() <= 1.0 + 1e-6 && position.z.abs() <= 1.0 + 1e-6);
+        }
+        assert!(grid.normals.is_some() && grid.tangents.is_some());
+    }
+
+    #[test]
+    fn catmull_clark_splits_quads() {
+        let cube = welded_cube();
+        let quads = cube.quad_faces().unwrap();
+        assert_eq!(quads.len(), 6);
+
+        let smooth = cube.subdivide_catmull_clark_faces(&quads, PI);
+        assert_eq!(smooth.vertex_count(), 8 + 12 + 6);
+        assert_eq!(smooth.triangle_count(), 48);
+        assert_eq!(smooth.quad_faces().unwrap().len(), 24);
+        assert!(smooth.validate().is_closed_manifold());
+        for corner in &smooth.positions[..8] {
+            assert!((corner.x.abs() - 5.0 / 18.0).abs() < 1e-5);
+        }
+
+        let sharp = cube.subdivide_catmull_clark_faces(&quads, PI / 4.0);
+        assert!(sharp.positions.iter().all(|&position| on_cube(position)));
+        let twice = sharp.subdivide_catmull_clark_faces(&sharp.quad_faces().unwrap(), PI / 4.0);
+        assert_eq!(twice.triangle_count(), 4 * 48);
+    }
+
+    #[test]
+    fn catmull_clark_splits_triangles_in_any_order() {
+        let grid = plane(2.0, 2.0, 2, 2);
+        let mut shuffled = grid.clone();
+        shuffled.indices = grid
+            .indices
+            .chunks_exact(3)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        assert!(shuffled.quad_faces().is_none());
+
+        let [ordered, shuffled] = [grid, shuffled].map(|mesh| mesh.subdivide_catmull_clark(PI));
+        assert_eq!(ordered.triangle_count(), 8 * 6);
+        assert_eq!(ordered.vertex_count(), shuffled.vertex_count());
+        for position in &ordered.positions {
+            assert!(shuffled
+                .positions
+                .iter()
+                .any(|&other| (other - position).magnitude() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn midpoints_of_large_indices_are_distinct() {
+        // Pairing the two indices into a u32 key overflows at this size.
+        let mut positions = vec![Vector3::new(0.0, 0.0, 0.0); 100_001];
+        positions[70_000] = Vector3::new(0.0, 0.0, 0.0);
+        positions[70_001] = Vector3::new(2.0, 0.0, 0.0);
+        positions[100_000] = Vector3::new(0.0, 2.0, 0.0);
+
+        let (positions, indices) = subdivide_midpoint(&positions, &[70_000, 70_001, 100_000]);
+        assert_eq!(positions.len(), 100_004);
+        assert_eq!(indices.len(), 12);
+        let midpoints = &positions[100_001..];
+        assert!(midpoints.contains(&Vector3::new(1.0, 0.0, 0.0)));
+        assert!(midpoints.contains(&Vector3::new(0.0, 1.0, 0.0)));
+        assert!(midpoints.contains(&Vector3::new(1.0, 1.0, 0.0)));
+    }
+}
@@ -0,0 +1,153 @@
+use cgmath::{prelude::*, Vector2};
+use std::collections::HashMap;
+
+use super::{compute_spherical_uv, Mesh};
+
+/// Change in `u` over a full turn around Y in [`compute_spherical_uv`].
+const U_PERIOD: f32 = 2.0;
+
+impl Mesh {
+    /// A copy of the mesh with the UVs of [`compute_spherical_uvs`](Mesh::compute_spherical_uvs),
+    /// fixed where they cannot be shared between triangles.
+    ///
+    /// Triangles crossing the seam get `u` values past the end of the
+    /// texture instead of wrapping around it, so the texture needs to repeat
+    /// horizontally. Vertices on the Y axis, where `u` is undefined, take the
+    /// `u` of the rest of their triangle. Vertices that need another UV are
+    /// duplicated after the others, so the copy is re-indexed. Other
+    /// attributes are carried over and tangents, if any, are recomputed.
+    pub fn with_spherical_uvs(&self) -> Mesh {
+        let mut sources: Vec<u32> = (0..self.positions.len() as u32).collect();
+        let spherical: Vec<Vector2<f32>> = self
+            .positions
+            .iter()
+            .map(|&position| compute_spherical_uv(position))
+            .collect();
+        let mut uvs = spherical.clone();
+        // A vertex keeps its index for the first UV it gets.
+        let mut assigned = vec![false; self.positions.len()];
+        let mut copies: HashMap<(u32, [u32; 2]), u32> = HashMap::new();
+        let mut indices = Vec::with_capacity(self.indices.len());
+
+        for triangle in self.indices.chunks_exact(3) {
+            let mut corners = [0, 1, 2].map(|k| spherical[triangle[k] as usize]);
+            let on_pole = [0, 1, 2].map(|k| {
+                let position = self.positions[triangle[k] as usize];
+                position.x.hypot(position.z) <= 1e-6 * position.magnitude()
+            });
+
+            let around = || (0..3).filter(|&k| !on_pole[k]);
+            let min_u = around().map(|k| corners[k].x).fold(f32::INFINITY, f32::min);
+            let max_u = around()
+                .map(|k| corners[k].x)
+                .fold(f32::NEG_INFINITY, f32::max);
+            if max_u - min_u > U_PERIOD / 2.0 {
+                for k in around() {
+                    if corners[k].x < U_PERIOD / 2.0 {
+                        corners[k].x += U_PERIOD;
+                    }
+                }
+            }
+            if around().count() > 0 {
+                let u = around().map(|k| corners[k].x).sum::<f32>() / around().count() as f32;
+                for k in (0..3).filter(|&k| on_pole[k]) {
+                    corners[k].x = u;
+                }
+            }
+
+            for (k, uv) in corners.into_iter().enumerate() {
+                let vertex = triangle[k];
+                if !assigned[vertex as usize] {
+                    assigned[vertex as usize] = true;
+                    uvs[vertex as usize] = uv;
+                }
+                if uv == uvs[vertex as usize] {
+                    indices.push(vertex);
+                    continue;
+                }
+                let copy = *copies
+                    .entry((vertex, [uv.x.to_bits(), uv.y.to_bits()]))
+                    .or_insert_with(|| {
+                        sources.push(vertex);
+                        uvs.push(uv);
+                        sources.len() as u32 - 1
+                    });
+                indices.push(copy);
+            }
+        }
+
+        let mut mesh = self.remapped(&sources, indices);
+        mesh.uvs = Some(uvs);
+        if mesh.tangents.is_some() {
+            mesh.compute_tangents();
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::icosphere;
+
+    fn sphere() -> Mesh {
+        let (positions, indices) = icosphere(2);
+        Mesh::new(positions, indices)
+    }
+
+    fn u_spread(mesh: &Mesh) -> f32 {
+        let uvs = mesh.uvs.as_ref().unwrap();
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let u = [0, 1, 2].map(|k| uvs[triangle[k] as usize].x);
+                u[0].max(u[1]).max(u[2]) - u[0].min(u[1]).min(u[2])
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn shared_uvs_zip_the_seam() {
+        let mut mesh = sphere();
+        mesh.compute_spherical_uvs();
+        assert!(u_spread(&mesh) > U_PERIOD / 2.0);
+    }
+
+    #[test]
+    fn no_triangle_spans_the_texture() {
+        let mesh = sphere();
+        let fixed = mesh.with_spherical_uvs();
+        assert!(u_spread(&fixed) < 0.5, "{}", u_spread(&fixed));
+
+        // Same triangles, with UVs moved by whole turns off the poles.
+        assert!(fixed.vertex_count() > mesh.vertex_count());
+        let uvs = fixed.uvs.as_ref().unwrap();
+        for (old, new) in mesh.indices.iter().zip(&fixed.indices) {
+            let position = mesh.positions[*old as usize];
+            assert_eq!(position, fixed.positions[*new as usize]);
+            let (uv, expected) = (uvs[*new as usize], compute_spherical_uv(position));
+            assert_eq!(uv.y, expected.y);
+            if position.x != 0.0 || position.z != 0.0 {
+                assert!([0.0, U_PERIOD].contains(&(uv.x - expected.x)));
+            }
+        }
+    }
+
+    #[test]
+    fn poles_get_one_copy_per_triangle() {
+        let mesh = sphere();
+        let fixed = mesh.with_spherical_uvs();
+        let uvs = fixed.uvs.as_ref().unwrap();
+        let north: Vec<Vector2<f32>> = (0..fixed.vertex_count())
+            .filter(|&vertex| {
+                let position = fixed.positions[vertex];
+                (position.x, position.z) == (0.0, 0.0) && position.y > 0.0
+            })
+            .map(|vertex| uvs[vertex])
+            .collect();
+        // One for each triangle around the pole, an edge midpoint of the
+        // icosahedron, at the top of the texture.
+        assert_eq!(north.len(), 6);
+        assert!(north.iter().all(|uv| uv.y == 0.0));
+    }
+}
@@ -0,0 +1,232 @@
+use cgmath::Vector3;
+use std::{collections::HashMap, fmt};
+
+use super::{cleanup::is_degenerate, edge_key, Mesh};
+
+/// Problems found by [`validate`] in a triangle list, and its topology.
+///
+/// Triangles with an out-of-range index are only reported as such and are
+/// left out of every other check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Number of indices left over after the last full triangle.
+    pub trailing_indices: usize,
+    /// Positions in the index list of the indices with no matching vertex.
+    pub out_of_range_indices: Vec<usize>,
+    /// Vertices with a NaN or infinite coordinate.
+    pub non_finite_positions: Vec<u32>,
+    /// Triangles, by number, using a vertex twice or with no area.
+    pub degenerate_triangles: Vec<usize>,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: Vec<[u32; 2]>,
+    /// Edges used by a single triangle.
+    pub boundary_edges: Vec<[u32; 2]>,
+    /// Edges shared by two triangles going through them in the same
+    /// direction, meaning one of the triangles is flipped.
+    pub inconsistent_edges: Vec<[u32; 2]>,
+    /// Vertices used by at least one triangle.
+    pub vertex_count: usize,
+    pub edge_count: usize,
+    pub triangle_count: usize,
+}
+
+impl ValidationReport {
+    /// V - E + F. It is 2 for a closed surface without holes, 0 for a torus
+    /// and 1 for a disc.
+    pub fn euler_characteristic(&self) -> i64 {
+        self.vertex_count as i64 - self.edge_count as i64 + self.triangle_count as i64
+    }
+
+    /// Whether the data can be drawn and processed safely: every index is in
+    /// range, every position is finite and no index is left over.
+    pub fn is_valid(&self) -> bool {
+        self.trailing_indices == 0
+            && self.out_of_range_indices.is_empty()
+            && self.non_finite_positions.is_empty()
+    }
+
+    /// Whether the triangles form consistently wound, closed surfaces where
+    /// each edge is shared by exactly two triangles.
+    pub fn is_closed_manifold(&self) -> bool {
+        self.is_valid()
+            && self.degenerate_triangles.is_empty()
+            && self.non_manifold_edges.is_empty()
+            && self.boundary_edges.is_empty()
+            && self.inconsistent_edges.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} vertices, {} edges, {} triangles, Euler characteristic {}",
+            self.vertex_count,
+            self.edge_count,
+            self.triangle_count,
+            self.euler_characteristic()
+        )?;
+        let problems = [
+            (self.trailing_indices, "trailing indices"),
+            (self.out_of_range_indices.len(), "out-of-range indices"),
+            (self.non_finite_positions.len(), "non-finite positions"),
+            (self.degenerate_triangles.len(), "degenerate triangles"),
+            (self.non_manifold_edges.len(), "non-manifold edges"),
+            (self.boundary_edges.len(), "boundary edges"),
+            (self.inconsistent_edges.len(), "inconsistently wound edges"),
+        ];
+        for (count, problem) in problems {
+            if count > 0 {
+                write!(f, ", {count} {problem}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks a triangle list without panicking on bad input, unlike the other
+/// functions of this module.
+pub fn validate(positions: &[Vector3<f32>], indices: &[u32]) -> ValidationReport {
+    let mut report = ValidationReport {
+        trailing_indices: indices.len() % 3,
+        non_finite_positions: (0..positions.len() as u32)
+            .filter(|&vertex| {
+                let position = positions[vertex as usize];
+                !(position.x.is_finite() && position.y.is_finite() && position.z.is_finite())
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    // Each edge, from its smallest vertex to its largest, with how many
+    // triangles go through it in that direction and in the other.
+    let mut edges: HashMap<u64, (usize, usize)> = HashMap::new();
+    let mut used = vec![false; positions.len()];
+
+    for (number, triangle) in indices.chunks_exact(3).enumerate() {
+        let out_of_range: Vec<usize> = (0..3)
+            .filter(|&k| triangle[k] as usize >= positions.len())
+            .map(|k| number * 3 + k)
+            .collect();
+        if !out_of_range.is_empty() {
+            report.out_of_range_indices.extend(out_of_range);
+            continue;
+        }
+
+        let triangle = [triangle[0], triangle[1], triangle[2]];
+        report.triangle_count += 1;
+        if is_degenerate(positions, triangle) {
+            report.degenerate_triangles.push(number);
+        }
+        for k in 0..3 {
+            let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+            used[a as usize] = true;
+            if a == b {
+                continue;
+            }
+            let uses = edges.entry(edge_key(a, b)).or_default();
+            if a < b {
+                uses.0 += 1;
+            } else {
+                uses.1 += 1;
+            }
+        }
+    }
+
+    report.vertex_count = used.into_iter().filter(|&used| used).count();
+    report.edge_count = edges.len();
+    for (key, (forward, backward)) in edges {
+        let (a, b) = ((key >> 32) as u32, key as u32);
+        match forward + backward {
+            1 => report.boundary_edges.push([a, b]),
+            2 if forward != backward => report.inconsistent_edges.push([a, b]),
+            2 => {}
+            _ => report.non_manifold_edges.push([a, b]),
+        }
+    }
+    for edges in [
+        &mut report.boundary_edges,
+        &mut report.inconsistent_edges,
+        &mut report.non_manifold_edges,
+    ] {
+        edges.sort_unstable();
+    }
+
+    report
+}
+
+impl Mesh {
+    /// Checks the positions and indices of the mesh, see [`validate`].
+    pub fn validate(&self) -> ValidationReport {
+        validate(&self.positions, &self.indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::geometry::{cuboid, disc, icosphere, torus};
+
+    fn welded(mesh: Mesh) -> Mesh {
+        let mut mesh = Mesh::new(mesh.positions, mesh.indices);
+        mesh.weld(1e-5);
+        mesh
+    }
+
+    #[test]
+    fn closed_surfaces() {
+        let (positions, indices) = icosphere(2);
+        let report = validate(&positions, &indices);
+        assert!(report.is_closed_manifold(), "{report}");
+        assert_eq!(report.euler_characteristic(), 2);
+
+        // The seams of the primitives need welding first.
+        let cube = welded(cuboid(1.0, 1.0, 1.0));
+        assert!(cube.validate().is_closed_manifold());
+
+        let ring = welded(torus(1.0, 0.25, 16, 8));
+        assert!(ring.validate().is_closed_manifold());
+        assert_eq!(ring.validate().euler_characteristic(), 0);
+    }
+
+    #[test]
+    fn open_surfaces() {
+        let report = welded(disc(1.0, 12)).validate();
+        assert!(report.is_valid());
+        assert!(!report.is_closed_manifold());
+        assert_eq!(report.boundary_edges.len(), 12);
+        assert_eq!(report.euler_characteristic(), 1);
+    }
+
+    #[test]
+    fn reports_problems_without_panicking() {
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(f32::NAN, 0.0, 0.0),
+        ];
+        let indices = vec![
+            0, 1, 2, //
+            1, 2, 3, // goes through 1-2 the same way as the first
+            1, 0, 3, //
+            0, 1, 3, // third triangle on 0-1 and 1-3
+            3, 3, 3, // repeated vertex
+            0, 9, 2, // out of range
+            4, // left over
+        ];
+        let report = validate(&positions, &indices);
+
+        assert!(!report.is_valid());
+        assert_eq!(report.trailing_indices, 1);
+        assert_eq!(report.out_of_range_indices, vec![16]);
+        assert_eq!(report.non_finite_positions, vec![4]);
+        assert_eq!(report.degenerate_triangles, vec![4]);
+        assert_eq!(report.non_manifold_edges, vec![[0, 1], [1, 3]]);
+        assert_eq!(report.inconsistent_edges, vec![[1, 2]]);
+        assert_eq!(report.boundary_edges, vec![[0, 2], [2, 3]]);
+        assert_eq!(report.triangle_count, 5);
+        assert_eq!(report.vertex_count, 4);
+    }
+}
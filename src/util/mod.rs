@@ -1,2 +1,5 @@
+pub mod compute;
 pub mod geometry;
 pub mod orbit_camera;
+pub mod render_target;
+pub mod texture;
@@ -0,0 +1,252 @@
+use eframe::wgpu;
+
+use crate::runner::Context;
+
+/// An offscreen framebuffer: a color texture and an optional depth texture
+/// that can be rendered to in [`App::encode`](crate::App::encode) and
+/// sampled from a later pass through [`RenderTarget::bind_group`].
+pub struct RenderTarget {
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    color_texture: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth_texture: Option<wgpu::Texture>,
+    depth_view: Option<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl RenderTarget {
+    pub fn new(
+        context: &Context,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Self {
+        let (color_texture, color_view) = create_texture(
+            context,
+            "Render Target Color Texture",
+            width,
+            height,
+            format,
+        );
+        let (depth_texture, depth_view) = match depth_format {
+            Some(depth_format) => {
+                let (texture, view) = create_texture(
+                    context,
+                    "Render Target Depth Texture",
+                    width,
+                    height,
+                    depth_format,
+                );
+                (Some(texture), Some(view))
+            }
+            None => (None, None),
+        };
+
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Render Target Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = context
+            .device()
+            .create_bind_group_layout(&RenderTarget::desc());
+        let bind_group = create_bind_group(context, &bind_group_layout, &color_view, &sampler);
+
+        Self {
+            width,
+            height,
+            format,
+            depth_format,
+            color_texture,
+            color_view,
+            depth_texture,
+            depth_view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Recreates the textures with the new size, typically from
+    /// [`App::resize`](crate::App::resize). The content is lost and the bind
+    /// group is replaced.
+    pub fn resize(&mut self, context: &Context, width: u32, height: u32) {
+        if width == self.width && height == self.height {
+            return;
+        }
+        *self = Self::new(context, width, height, self.format, self.depth_format);
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_format
+    }
+
+    pub fn color_texture(&self) -> &wgpu::Texture {
+        &self.color_texture
+    }
+
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color_view
+    }
+
+    pub fn depth_texture(&self) -> Option<&wgpu::Texture> {
+        self.depth_texture.as_ref()
+    }
+
+    pub fn depth_view(&self) -> Option<&wgpu::TextureView> {
+        self.depth_view.as_ref()
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn color_attachment(&self, clear: wgpu::Color) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.color_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear),
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    pub fn depth_stencil_attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+        self.depth_view
+            .as_ref()
+            .map(|view| wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            })
+    }
+
+    /// Starts a render pass that clears and draws into this target.
+    pub fn begin_render_pass<'encoder>(
+        &self,
+        encoder: &'encoder mut wgpu::CommandEncoder,
+        clear: wgpu::Color,
+    ) -> wgpu::RenderPass<'encoder> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Target Pass"),
+            color_attachments: &[Some(self.color_attachment(clear))],
+            depth_stencil_attachment: self.depth_stencil_attachment(),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+
+    /// Layout of the bind group returned by [`RenderTarget::bind_group`]:
+    /// the color texture at binding 0 and its sampler at binding 1. The color
+    /// format must be filterable.
+    pub fn desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Render Target Bind Group Layout"),
+        }
+    }
+}
+
+fn create_texture(
+    context: &Context,
+    label: &str,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = context.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+fn create_bind_group(
+    context: &Context,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    context
+        .device()
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("Render Target Bind Group"),
+        })
+}
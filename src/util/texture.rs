@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use eframe::wgpu;
+
+use crate::runner::Context;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Colors (albedo, UI images): the GPU converts to linear when sampling.
+    Srgb,
+    /// Data (normal maps, roughness, heights): the values are sampled as is.
+    Linear,
+}
+
+/// A 2D RGBA texture with its view, a sampler and a bind group matching
+/// [`Texture::desc`].
+pub struct Texture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Texture {
+    pub fn from_path(
+        context: &Context,
+        path: impl AsRef<Path>,
+        color_space: ColorSpace,
+        mipmaps: bool,
+    ) -> image::ImageResult<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)?.to_rgba8();
+        let label = path.to_string_lossy();
+        Ok(Self::from_image(
+            context,
+            &image,
+            &label,
+            color_space,
+            mipmaps,
+        ))
+    }
+
+    /// Decodes a PNG or JPEG file already loaded in memory, for instance
+    /// with `include_bytes!`.
+    pub fn from_bytes(
+        context: &Context,
+        bytes: &[u8],
+        label: &str,
+        color_space: ColorSpace,
+        mipmaps: bool,
+    ) -> image::ImageResult<Self> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        Ok(Self::from_image(
+            context,
+            &image,
+            label,
+            color_space,
+            mipmaps,
+        ))
+    }
+
+    pub fn from_image(
+        context: &Context,
+        image: &image::RgbaImage,
+        label: &str,
+        color_space: ColorSpace,
+        mipmaps: bool,
+    ) -> Self {
+        let (width, height) = image.dimensions();
+        let mip_level_count = if mipmaps {
+            mip_level_count(width, height)
+        } else {
+            1
+        };
+        let format = match color_space {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        };
+
+        let texture = context.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Mipmaps are downsampled on the CPU, each level from the previous one.
+        let mut level = image.clone();
+        for mip_level in 0..mip_level_count {
+            if mip_level > 0 {
+                level = image::imageops::resize(
+                    &level,
+                    (level.width() / 2).max(1),
+                    (level.height() / 2).max(1),
+                    image::imageops::FilterType::Triangle,
+                );
+            }
+            context.queue().write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level.width()),
+                    rows_per_image: Some(level.height()),
+                },
+                wgpu::Extent3d {
+                    width: level.width(),
+                    height: level.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = context.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Texture Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = context.device().create_bind_group_layout(&Texture::desc());
+        let bind_group = context
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+                label: Some("Texture Bind Group"),
+            });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            bind_group,
+        }
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    pub fn desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Texture Bind Group Layout"),
+        }
+    }
+}
+
+/// Number of levels down to 1x1 for a full mipmap chain.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::RunnerBuilder;
+
+    #[test]
+    fn mip_level_counts() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+    }
+
+    #[test]
+    fn loads_png_with_mipmaps() {
+        let Ok(runner) = RunnerBuilder::new().build_headless() else {
+            eprintln!("no WGPU adapter available, skipping");
+            return;
+        };
+
+        let image = image::RgbaImage::from_pixel(64, 32, image::Rgba([255, 0, 0, 255]));
+        let mut bytes = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+
+        let texture =
+            Texture::from_bytes(&runner.context(), &bytes, "Red", ColorSpace::Srgb, true).unwrap();
+        assert_eq!((texture.width(), texture.height()), (64, 32));
+        assert_eq!(texture.texture().mip_level_count(), 7);
+        assert_eq!(
+            texture.texture().format(),
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+    }
+}
@@ -0,0 +1,62 @@
+#[path = "../examples/cube/cube_app.rs"]
+mod cube_app;
+#[path = "../examples/instances/instances_app.rs"]
+mod instances_app;
+#[path = "../examples/shading/shading_app.rs"]
+mod shading_app;
+#[path = "../examples/triangle/triangle_app.rs"]
+mod triangle_app;
+#[path = "../examples/wireframe/wireframe_app.rs"]
+mod wireframe_app;
+
+use wgpu_bootstrap::{
+    testing::{assert_render_matches, Tolerance},
+    App, Context, HeadlessRunner, Runner,
+};
+
+fn runner(depth_buffer: u8) -> Option<HeadlessRunner> {
+    match Runner::builder()
+        .size(160, 120)
+        .depth_buffer(depth_buffer)
+        .build_headless()
+    {
+        Ok(runner) => Some(runner),
+        Err(error) => {
+            eprintln!("Skipping golden image test: {error}");
+            None
+        }
+    }
+}
+
+fn check<A: App>(name: &str, depth_buffer: u8, app_creator: impl FnOnce(&Context) -> A) {
+    let Some(runner) = runner(depth_buffer) else {
+        return;
+    };
+    let mut app = app_creator(&runner.context());
+    assert_render_matches(name, &runner, &mut app, 2, Tolerance::default());
+}
+
+#[test]
+fn triangle() {
+    check("triangle", 0, triangle_app::TriangleApp::new);
+}
+
+#[test]
+fn cube() {
+    check("cube", 32, cube_app::CubeApp::new);
+}
+
+#[test]
+fn shading() {
+    check("shading", 32, shading_app::ShadingApp::new);
+}
+
+#[test]
+fn wireframe() {
+    check("wireframe", 32, wireframe_app::WireframeApp::new);
+}
+
+#[test]
+fn instances() {
+    check("instances", 32, instances_app::InstanceApp::new);
+}